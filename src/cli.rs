@@ -0,0 +1,310 @@
+use std::{collections::BTreeMap, error::Error, fs, path::{Path, PathBuf}};
+
+use bevy::math::Vec3;
+
+use crate::{interchange::{load_ship_document, save_ship_document}, ship_diff::{diff_parts, diff_report}, ship_merge::merge_ships, parsing::{load_save, load_save_partial, save_ship, Part, Ship}, parts::{bounding_box, PartRegistry}};
+
+const USAGE: &str = "usage:
+  naval_sketch <steam_path> <ship.na>                        open the editor
+  naval_sketch info <ship.na> [--steam <steam_path>]         part counts and dimensions
+  naval_sketch validate <ship.na|folder>... [--steam <steam_path>]
+  naval_sketch convert <input> <output>                      convert between .na, .json and .ron by file extension
+  naval_sketch normalize <input> [output]                    sort parts and tidy values, in place if no output
+  naval_sketch diff <old> <new>                              list added, removed and changed parts
+  naval_sketch merge <base> <ours> <theirs> [output]         three way merge into output, or ours if no output";
+
+/// Runs a headless subcommand if the arguments name one, returning the exit code
+pub fn run(args: &[String]) -> Option<i32> {
+    let Some(subcommand) = args.get(1) else {
+        println!("{}",USAGE);
+        return Some(2);
+    };
+
+    let mut steam_path: Option<PathBuf> = None;
+    let mut positional: Vec<&String> = Vec::new();
+    let mut rest = args.iter().skip(2);
+    while let Some(arg) = rest.next() {
+        if arg == "--steam" {
+            let Some(path) = rest.next() else {
+                println!("--steam needs a path\n{}",USAGE);
+                return Some(2);
+            };
+            steam_path = Some(PathBuf::from(path));
+        } else {
+            positional.push(arg);
+        }
+    }
+    let part_registry = match steam_path.map(|steam_path| PartRegistry::load(&steam_path)).transpose() {
+        Ok(part_registry) => part_registry,
+        Err(error) => {
+            println!("error: couldn't load the parts: {}",error);
+            return Some(1);
+        }
+    };
+
+    let result = match subcommand.as_str() {
+        "info" if positional.len() == 1 => info(Path::new(positional[0]), part_registry.as_ref()),
+        "validate" if !positional.is_empty() => validate(&positional, part_registry.as_ref()),
+        "convert" if positional.len() == 2 => convert(Path::new(positional[0]), Path::new(positional[1])),
+        "normalize" if positional.len() == 1 || positional.len() == 2 => {
+            normalize(Path::new(positional[0]), Path::new(positional.get(1).unwrap_or(&positional[0])))
+        }
+        "diff" if positional.len() == 2 => diff(Path::new(positional[0]), Path::new(positional[1])),
+        "merge" if positional.len() == 3 || positional.len() == 4 => merge(
+            Path::new(positional[0]),
+            Path::new(positional[1]),
+            Path::new(positional[2]),
+            Path::new(positional.get(3).unwrap_or(&positional[1]))
+        ),
+        "info" | "validate" | "convert" | "normalize" | "diff" | "merge" | "help" | "--help" | "-h" => {
+            println!("{}",USAGE);
+            return Some(2);
+        }
+        _ => {
+            if args.len() < 3 {
+                println!("{}",USAGE);
+                return Some(2);
+            }
+            //not a subcommand, open the editor
+            return None;
+        }
+    };
+
+    match result {
+        Ok(code) => Some(code),
+        Err(error) => {
+            println!("error: {}",error);
+            Some(1)
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ShipFormat {
+    NavalArt,
+    //both json and ron, see interchange.rs
+    Document,
+}
+
+impl ShipFormat {
+    pub fn of(path: &Path) -> Result<ShipFormat, Box<dyn Error>> {
+        match path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_lowercase()).as_deref() {
+            Some("na") | Some("xml") => Ok(ShipFormat::NavalArt),
+            Some("json") | Some("ron") => Ok(ShipFormat::Document),
+            _ => Err(format!("don't know what format {:?} is, use .na, .json or .ron", path).into()),
+        }
+    }
+
+    pub fn load(&self, path: &Path) -> Result<Ship, Box<dyn Error>> {
+        match self {
+            ShipFormat::NavalArt => Ok(load_save(path)?),
+            ShipFormat::Document => load_ship_document(path),
+        }
+    }
+
+    pub fn save(&self, path: &Path, ship: &Ship) -> Result<(), Box<dyn Error>> {
+        match self {
+            ShipFormat::NavalArt => save_ship(path, ship),
+            ShipFormat::Document => save_ship_document(path, ship),
+        }
+    }
+}
+
+fn part_type(part: &Part) -> &'static str {
+    match part {
+        Part::Normal(_) => "normal",
+        Part::AdjustableHull(_,_) => "adjustable hull",
+        Part::Turret(_,_) => "turret",
+    }
+}
+
+fn info(path: &Path, part_registry: Option<&PartRegistry>) -> Result<i32, Box<dyn Error>> {
+    let ship = ShipFormat::of(path)?.load(path)?;
+
+    println!("{}",path.display());
+    if !ship.header.metadata.name.is_empty() {
+        println!("name: {}",ship.header.metadata.name);
+    }
+    if !ship.header.metadata.game_version.is_empty() {
+        println!("game version: {}",ship.header.metadata.game_version);
+    }
+    println!("parts: {}",ship.parts.len());
+
+    let mut by_type: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut by_id: BTreeMap<i32, usize> = BTreeMap::new();
+    for part in &ship.parts {
+        *by_type.entry(part_type(part)).or_default() += 1;
+        *by_id.entry(part.base_part().id).or_default() += 1;
+    }
+    println!("hulls: {}",by_type.get("adjustable hull").unwrap_or(&0));
+    println!("by type:");
+    for (part_type, count) in &by_type {
+        println!("  {:<16} {}",part_type,count);
+    }
+    println!("by id:");
+    for (id, count) in &by_id {
+        let name = part_registry.and_then(|registry| registry.parts.get(id)).map(|part_data| part_data.part_name.get_fallback()).unwrap_or("");
+        println!("  {:<8} {:<6} {}",id,count,name);
+    }
+
+    let Some((min, max)) = bounding_box(&ship.parts, part_registry) else { return Ok(0); };
+    if part_registry.is_none() {
+        println!("bounding box of part origins (pass --steam for part sizes):");
+    } else {
+        println!("bounding box:");
+    }
+    println!("  min {} {} {}",min.x,min.y,min.z);
+    println!("  max {} {} {}",max.x,max.y,max.z);
+    let size = max-min;
+    println!("  length {} beam {} height {}",size.z,size.x,size.y);
+    return Ok(0);
+}
+
+fn ship_files(paths: &[&String]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for path in paths {
+        let path = PathBuf::from(path);
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = fs::read_dir(&path)?.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+            entries.sort();
+            let entries: Vec<String> = entries.into_iter()
+                .filter(|entry| entry.is_dir() || entry.extension().is_some_and(|extension| extension == "na"))
+                .map(|entry| entry.to_string_lossy().to_string())
+                .collect();
+            files.extend(ship_files(&entries.iter().collect::<Vec<&String>>())?);
+        } else {
+            files.push(path);
+        }
+    }
+    return Ok(files);
+}
+
+/// Problems with a ship that parsed fine but that the game won't like
+pub fn part_problems(parts: &[Part], part_registry: Option<&PartRegistry>) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (index, part) in parts.iter().enumerate() {
+        let base_part = part.base_part();
+        if let Some(registry) = part_registry {
+            if !registry.parts.contains_key(&base_part.id) {
+                problems.push(format!("part #{}: unknown part id {}",index,base_part.id));
+            }
+        }
+        if !(base_part.position.is_finite() && base_part.rotation.is_finite() && base_part.scale.is_finite()) {
+            problems.push(format!("part #{}: position, rotation or scale isn't a finite number",index));
+        }
+        if base_part.scale.cmpeq(Vec3::ZERO).any() {
+            problems.push(format!("part #{}: has a scale of 0",index));
+        }
+        if let Part::AdjustableHull(_,adjustable_hull) = part {
+            if adjustable_hull.length <= 0.0 || adjustable_hull.height <= 0.0 {
+                problems.push(format!("part #{}: adjustable hull has no length or height",index));
+            }
+        }
+    }
+    return problems;
+}
+
+fn validate(paths: &[&String], part_registry: Option<&PartRegistry>) -> Result<i32, Box<dyn Error>> {
+    let mut failed = 0;
+    let files = ship_files(paths)?;
+    for file in &files {
+        let mut problems: Vec<String> = Vec::new();
+        match load_save_partial(file) {
+            Ok((ship, parse_problems)) => {
+                problems.extend(parse_problems.iter().map(|problem| problem.to_string()));
+                problems.extend(part_problems(&ship.parts, part_registry));
+            }
+            Err(error) => problems.push(error.to_string()),
+        }
+
+        if problems.is_empty() {
+            println!("ok   {}",file.display());
+        } else {
+            failed += 1;
+            println!("FAIL {}",file.display());
+            for problem in &problems {
+                println!("  {}",problem);
+            }
+        }
+    }
+    println!("{} of {} ships had problems",failed,files.len());
+    return Ok(if failed == 0 { 0 } else { 1 });
+}
+
+fn convert(input: &Path, output: &Path) -> Result<i32, Box<dyn Error>> {
+    let ship = ShipFormat::of(input)?.load(input)?;
+    ShipFormat::of(output)?.save(output, &ship)?;
+    println!("wrote {} parts to {}",ship.parts.len(),output.display());
+    return Ok(0);
+}
+
+fn tidy(value: f32) -> f32 {
+    let rounded = (value*10000.0).round()/10000.0;
+    //no negative zeros
+    return if rounded == 0.0 { 0.0 } else { rounded };
+}
+
+fn tidy_vec3(vec: Vec3) -> Vec3 {
+    return Vec3::new(tidy(vec.x), tidy(vec.y), tidy(vec.z));
+}
+
+/// Puts a ship into a canonical form so that equivalent ships save to identical files
+pub fn normalize_ship(ship: &mut Ship) {
+    for part in &mut ship.parts {
+        let base_part = part.base_part_mut();
+        base_part.position = tidy_vec3(base_part.position);
+        base_part.rotation = tidy_vec3(base_part.rotation.map(|angle| angle.rem_euclid(360.0)));
+        base_part.scale = tidy_vec3(base_part.scale);
+        if let Part::AdjustableHull(_,adjustable_hull) = part {
+            for value in [
+                &mut adjustable_hull.length, &mut adjustable_hull.height,
+                &mut adjustable_hull.front_width, &mut adjustable_hull.back_width,
+                &mut adjustable_hull.front_spread, &mut adjustable_hull.back_spread,
+                &mut adjustable_hull.top_roundness, &mut adjustable_hull.bottom_roundness,
+                &mut adjustable_hull.height_scale, &mut adjustable_hull.height_offset,
+            ] {
+                *value = tidy(*value);
+            }
+        }
+    }
+    ship.parts.sort_by(|a, b| {
+        let (a, b) = (a.base_part(), b.base_part());
+        a.id.cmp(&b.id)
+            .then(a.position.z.total_cmp(&b.position.z))
+            .then(a.position.y.total_cmp(&b.position.y))
+            .then(a.position.x.total_cmp(&b.position.x))
+    });
+}
+
+fn normalize(input: &Path, output: &Path) -> Result<i32, Box<dyn Error>> {
+    let mut ship = ShipFormat::of(input)?.load(input)?;
+    normalize_ship(&mut ship);
+    ShipFormat::of(output)?.save(output, &ship)?;
+    println!("normalized {} parts into {}",ship.parts.len(),output.display());
+    return Ok(0);
+}
+
+/// Exits with 1 when the ships differ, like diff does
+fn diff(old_path: &Path, new_path: &Path) -> Result<i32, Box<dyn Error>> {
+    let old = ShipFormat::of(old_path)?.load(old_path)?;
+    let new = ShipFormat::of(new_path)?.load(new_path)?;
+    let diff = diff_parts(&old.parts, &new.parts);
+    print!("{}",diff_report(&old.parts, &new.parts, &diff));
+    return Ok(if diff.is_empty() { 0 } else { 1 });
+}
+
+/// Works as a git merge driver, `naval_sketch merge %O %A %B`, which hands over temporary files without a .na extension
+fn merge(base_path: &Path, ours_path: &Path, theirs_path: &Path, output: &Path) -> Result<i32, Box<dyn Error>> {
+    let format_of = |path: &Path| ShipFormat::of(path).unwrap_or(ShipFormat::NavalArt);
+    let base = format_of(base_path).load(base_path)?;
+    let ours = format_of(ours_path).load(ours_path)?;
+    let theirs = format_of(theirs_path).load(theirs_path)?;
+
+    let result = merge_ships(&base, &ours, &theirs);
+    format_of(output).save(output, &result.ship)?;
+    for conflict in &result.conflicts {
+        println!("conflict: {}",conflict);
+    }
+    println!("merged {} parts into {} with {} conflicts",result.ship.parts.len(),output.display(),result.conflicts.len());
+    return Ok(if result.conflicts.is_empty() { 0 } else { 1 });
+}
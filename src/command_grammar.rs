@@ -0,0 +1,170 @@
+//! Vim style commands for translation mode, `[count]operator[count]target` like `x2w`.
+//!
+//! With the default keymap the operators are `x` delete, `y` yank, `c` recolor to the latest
+//! selected part's color and `=` align onto the latest selected part.
+//! Targets: the operator again (`xx`, `yy`) for the selection, `w a s d q e` for the adjacent
+//! hulls in that camera relative direction, `g` for everything touching the selection and
+//! `*` for every part with the same id as a selected part.
+//! Anything else is a plain `[count]command` looked up in the mode's `CommandTree`.
+//! Either can start with `"a` to pick the register it yanks into or pastes from.
+
+use std::{fmt::Display, sync::LazyLock};
+
+use bevy::math::Dir3;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{editor::{CommandMode, CommandTree}, keymap::{KeyAction, Keymap, TargetKey}, registers::is_register};
+
+//`[count]command`, parsed on every keystroke so it's only compiled once
+static SIMPLE_COMMAND: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\d*(\.\d*)?)?([a-zA-Z ]+)?$").unwrap());
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operator {
+    Delete,
+    Yank,
+    Recolor,
+    Align,
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"{}",match self {
+            Operator::Delete => "delete",
+            Operator::Yank => "yank",
+            Operator::Recolor => "recolor",
+            Operator::Align => "align",
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Target {
+    Selection,
+    //camera relative, like the smart moves
+    AdjacentHull {dir: Dir3, key: char},
+    Component,
+    SameId,
+}
+
+impl Target {
+    fn of(target_key: TargetKey, key: char) -> Target {
+        let dir = match target_key {
+            TargetKey::Forward => Dir3::NEG_Z,
+            TargetKey::Left => Dir3::NEG_X,
+            TargetKey::Back => Dir3::Z,
+            TargetKey::Right => Dir3::X,
+            TargetKey::Down => Dir3::NEG_Y,
+            TargetKey::Up => Dir3::Y,
+            TargetKey::Connected => return Target::Component,
+            TargetKey::SameId => return Target::SameId,
+        };
+        return Target::AdjacentHull { dir, key };
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Selection => write!(f,"selection"),
+            Target::AdjacentHull { key, .. } => write!(f,"hull {}",key),
+            Target::Component => write!(f,"connected"),
+            Target::SameId => write!(f,"same id"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParsedCommand {
+    //register is the `"a` before a command, for the ones that yank or paste
+    Simple {count: Option<f32>, command: String, action: KeyAction, register: Option<char>},
+    Operator {operator: Operator, count: usize, target: Target, register: Option<char>},
+    //a `:` line, without the `:`
+    Ex {line: String},
+}
+
+impl Display for ParsedCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParsedCommand::Simple { register: Some(register), .. } | ParsedCommand::Operator { register: Some(register), .. } => {
+                write!(f,"\"{}",register)?;
+            },
+            _ => {},
+        }
+        match self {
+            ParsedCommand::Simple { count: Some(count), command, .. } if *count != 1.0 => write!(f,"{}{}",count,command),
+            ParsedCommand::Simple { command, .. } => write!(f,"{}",command),
+            ParsedCommand::Operator { operator, count, target: target @ Target::AdjacentHull {..}, .. } => write!(f,"{}({} {})",operator,count,target),
+            ParsedCommand::Operator { operator, target, .. } => write!(f,"{}({})",operator,target),
+            ParsedCommand::Ex { line } => write!(f,":{}",line),
+        }
+    }
+}
+
+pub enum Parse {
+    Complete(ParsedCommand),
+    //a prefix of a command, wait for more keys
+    Incomplete,
+    Invalid,
+}
+
+pub fn parse_command(text: &str, mode: CommandMode, tree: &CommandTree, keymap: &Keymap) -> Parse {
+    let mut text = text;
+    let mut register: Option<char> = None;
+    if let Some(rest) = text.strip_prefix('"') {
+        let Some(register_key) = rest.chars().next() else { return Parse::Incomplete; };
+        if !is_register(register_key) {
+            return Parse::Invalid;
+        }
+        register = Some(register_key);
+        text = &rest[register_key.len_utf8()..];
+        if text.is_empty() {
+            return Parse::Incomplete;
+        }
+    }
+
+    if mode == CommandMode::Translation {
+        let operator_digits = text.bytes().take_while(|byte| byte.is_ascii_digit()).count();
+        let rest = &text[operator_digits..];
+        if let Some(operator_key) = rest.chars().next() {
+            if let Some(operator) = keymap.operators.get(&operator_key.to_string()) {
+                let rest = &rest[operator_key.len_utf8()..];
+                let target_digits = rest.bytes().take_while(|byte| byte.is_ascii_digit()).count();
+                let Some(target_key) = rest[target_digits..].chars().next() else { return Parse::Incomplete; };
+
+                let target = if target_key == operator_key {
+                    Target::Selection
+                } else {
+                    let Some(target) = keymap.targets.get(&target_key.to_string()) else { return Parse::Invalid; };
+                    Target::of(*target, target_key)
+                };
+
+                //counts multiply like vim's 2d3w
+                let operator_count = text[..operator_digits].parse::<usize>().unwrap_or(1);
+                let target_count = rest[..target_digits].parse::<usize>().unwrap_or(1);
+                return Parse::Complete(ParsedCommand::Operator {
+                    operator: *operator,
+                    count: operator_count*target_count,
+                    target,
+                    register,
+                });
+            }
+        }
+    }
+
+    let Some(captures) = SIMPLE_COMMAND.captures(text) else { return Parse::Invalid; };
+    let Some(command_match) = captures.get(3) else { return Parse::Incomplete; };
+
+    let is_command = tree.has_command(command_match.as_str().as_bytes());
+    if !is_command.0 {
+        return Parse::Invalid;
+    }
+    let Some(action) = is_command.1 else { return Parse::Incomplete; };
+    return Parse::Complete(ParsedCommand::Simple {
+        count: captures.get(1).and_then(|num_match| num_match.as_str().parse::<f32>().ok()),
+        command: command_match.as_str().to_owned(),
+        action,
+        register,
+    });
+}
@@ -3,9 +3,8 @@ use std::collections::VecDeque;
 
 use bevy_egui::EguiContexts;
 use enum_collections::{EnumMap, Enumerated};
-use regex::Regex;
 
-use crate::{cam_movement::EditorCamera, editor_actions::{EditorActionEvent, EditorSettingChange}, editor_ui::{render_gizmos, update_command_text, update_display_text, update_selected, EditorUiPlugin, Language, PropertiesDisplayData}, editor_utils::to_touch, parsing::{AdjustableHull, BasePart, Part, Turret}, parts::{base_part_to_bevy_transform, bevy_quat_to_unity, bevy_to_unity_translation, colored_part_material, generate_adjustable_hull_mesh, get_collider, BasePartMesh, BasePartMeshes, PartRegistry}, transform_gizmo::{config::TransformPivotPoint, GizmoOrientation}, transform_gizmo_bevy::{GizmoOptions, GizmoTarget}};
+use crate::{cam_movement::EditorCamera, command_grammar::{parse_command, Parse, ParsedCommand}, ex_commands::{complete, run_ex}, keymap::{KeyAction, Keymap}, macros::{parse_macro_command, MacroCommand, MacroParse, Macros, MACRO_DEPTH_LIMIT}, registers::Registers, placement::{update_placement, Placement}, mirror::MirrorPlane, symmetry::{link_mirrored_parts, mirror_changes, propagate_symmetry}, editor_actions::{EditorActionEvent, EditorSettingChange}, editor_ui::{render_gizmos, update_command_text, update_display_text, update_selected, EditorUiPlugin, Language, PropertiesDisplayData}, editor_utils::to_touch, parsing::{AdjustableHull, BasePart, Part, Turret}, parts::{base_part_to_bevy_transform, bevy_quat_to_unity, bevy_to_unity_translation, colored_part_material, generate_adjustable_hull_mesh, get_collider, BasePartMesh, BasePartMeshes, PartRegistry}, transform_gizmo::{config::TransformPivotPoint, GizmoOrientation}, transform_gizmo_bevy::{GizmoOptions, GizmoTarget}};
 use bevy::{app::{DynEq, Plugin, Startup, Update}, asset::{AssetPath, AssetServer, Assets, Handle, RenderAssetUsages}, color::{Color, Luminance, Srgba}, ecs::{event::{EventCursor, EventReader, Events}, query::Or, schedule::IntoSystemConfigs, system::{Local, SystemState}, world::{OnAdd, OnRemove, World}}, gltf::GltfAssetLabel, hierarchy::ChildBuilder, image::Image, input::{keyboard::{Key, KeyboardInput}, mouse::{MouseScrollUnit, MouseWheel}, ButtonInput}, math::{bounding::BoundingVolume, primitives::Cuboid, Dir3, Isometry3d, Quat, UVec2, Vec2, Vec3}, pbr::{DirectionalLight, MeshMaterial3d, StandardMaterial}, picking::{focus::HoverMap, mesh_picking::ray_cast::{MeshRayCast, RayCastSettings}, pointer::{PointerInteraction, PointerPress}, PickingBehavior}, prelude::{Added, BuildChildren, Camera, Camera3d, Changed, ChildBuild, Children, Commands, Component, DetectChanges, Down, Entity, Gizmos, HierarchyQueryExt, KeyCode, Mesh3d, Out, Over, Parent, Pointer, PointerButton, Query, RemovedComponents, Res, ResMut, Resource, Single, Text, Transform, Trigger, With}, reflect::List, render::{camera::{ClearColorConfig, OrthographicProjection, Projection, Viewport}, mesh::Mesh, view::RenderLayers}, scene::{SceneInstance, SceneRoot}, text::{TextColor, TextFont, TextLayout}, transform::components::GlobalTransform, ui::{widget::ImageNode, BackgroundColor, FlexDirection, FlexWrap, Node, Overflow, PositionType, ScrollPosition, TargetCamera, UiRect, Val}, utils::{default, HashMap}, window::Window};
 use rand::{rngs::SmallRng, Rng, SeedableRng};
 
@@ -52,8 +51,10 @@ impl Plugin for EditorPlugin {
         app.insert_resource(
             EditorData {
                 action_history: Vec::new(),
+                undone_actions: Vec::new(),
                 queued_actions: VecDeque::new(),
-                clipboard: Vec::new(),
+                registers: Registers::default(),
+                placing: None,
                 latest_selected: None,
                 language: Language::CN,
             }
@@ -66,6 +67,7 @@ impl Plugin for EditorPlugin {
                 gizmos_activated: true,
                 group_gizmos: true,
                 local_gizmo: true,
+                symmetry: false,
             }
         );
         app.insert_resource(
@@ -74,55 +76,19 @@ impl Plugin for EditorPlugin {
             }
         );
         
-        let mut command_trees = EnumMap::new_default();
-        
-        let mut command_tree = CommandTree::default();
-        command_tree.add_command(b"w");
-        command_tree.add_command(b"a");
-        command_tree.add_command(b"s");
-        command_tree.add_command(b"d");
-        command_tree.add_command(b"q");
-        command_tree.add_command(b"e");
-
-        command_tree.add_command(b"W");
-        command_tree.add_command(b"A");
-        command_tree.add_command(b"S");
-        command_tree.add_command(b"D");
-        command_tree.add_command(b"Q");
-        command_tree.add_command(b"E");
-
-        command_tree.add_command(b"y");
-        command_tree.add_command(b"p");
-
-        command_tree.add_command(b"f");
-        command_tree.add_command(b"F");
-
-        command_tree.add_command(b"L");
-
-        command_trees[CommandMode::Translation]=command_tree;
-
-
-        let mut command_tree = CommandTree::default();
-
-        command_tree.add_command(b"q");
-        command_tree.add_command(b"e");
-        command_tree.add_command(b"w");
-        command_tree.add_command(b"a");
-        command_tree.add_command(b"s");
-        command_tree.add_command(b"d");
-
-        command_tree.add_command(b" ");
-
-        command_tree.add_command(b"L");
-
-        command_trees[CommandMode::Attributes]=command_tree;
+        let keymap = Keymap::load();
 
         app.insert_resource(
             CommandData {
                 command_history: VecDeque::new(),
+                last_command: None,
+                ex_history: Vec::new(),
+                ex_history_index: None,
                 current_byte_index: 0,
                 current_command: Vec::new(),
-                commands: command_trees,
+                commands: keymap.command_trees(),
+                keymap,
+                macros: Macros::load(),
                 mode: CommandMode::Translation
             }
         );
@@ -130,6 +96,8 @@ impl Plugin for EditorPlugin {
         
         app.add_systems(Update, (
                 translate_floatings,
+                update_placement,
+                (link_mirrored_parts, propagate_symmetry).chain(),
                 update_selected,
                 (on_gizmo_update,on_part_changed).chain(),
                 command_typing,
@@ -156,8 +124,12 @@ pub enum CommandMode {
 #[derive(Resource)]
 pub struct EditorData {
     pub action_history: Vec<Action>,
+    //actions that were undone, most recent last, cleared when anything new is done
+    pub undone_actions: Vec<Action>,
     pub queued_actions: VecDeque<EditorActionEvent>, //use deque?
-    pub clipboard: Vec<Part>,
+    pub registers: Registers,
+    //parts waiting for a click to be placed
+    pub placing: Option<Placement>,
     pub language: Language,
     pub latest_selected: Option<Entity>
 }
@@ -170,26 +142,34 @@ pub struct EditorOptions {
     pub gizmos_activated: bool,
     pub group_gizmos: bool,
     pub local_gizmo: bool,
+    //edits are mirrored onto the linked part across the centreline
+    pub symmetry: bool,
 }
 
 #[derive(Resource)]
 pub struct CommandData {
-    pub command_history: VecDeque<String>,
+    pub command_history: VecDeque<ParsedCommand>,
+    pub last_command: Option<ParsedCommand>,
+    //oldest first
+    pub ex_history: Vec<String>,
+    pub ex_history_index: Option<usize>,
     pub current_byte_index: usize,
     pub current_command: Vec<u8>,
     pub commands: EnumMap<CommandMode,CommandTree,{CommandMode::SIZE}>,
+    pub keymap: Keymap,
+    pub macros: Macros,
     pub mode: CommandMode
 }
 
 pub struct CommandTree {
-    is_command: bool,
+    action: Option<KeyAction>,
     continuations: HashMap<u8,Box<CommandTree>>
 }
 
 impl Default for CommandTree {
     fn default() -> Self {
         CommandTree {
-            is_command: false,
+            action: None,
             continuations: HashMap::new()
 
         }
@@ -197,35 +177,75 @@ impl Default for CommandTree {
 }
 
 impl CommandTree {
-    fn add_command(&mut self, command_string: &[u8]) {
+    pub fn add_command(&mut self, command_string: &[u8], action: KeyAction) {
         if command_string.is_empty() {
-            self.is_command=true;
+            self.action=Some(action);
         }else{
             self.continuations.try_insert(command_string[0], Box::new(CommandTree::default()));
-            self.continuations.get_mut(&command_string[0]).unwrap().add_command(&command_string[1..]);
+            self.continuations.get_mut(&command_string[0]).unwrap().add_command(&command_string[1..], action);
         }
     }
-    fn has_command(&self, command_string: &[u8]) -> (bool, bool){
+    /// Whether the string starts a command, and the command's action if it's a whole one
+    pub fn has_command(&self, command_string: &[u8]) -> (bool, Option<KeyAction>){
         if command_string.is_empty() {
-            return (true, self.is_command);
+            return (true, self.action);
         }
         if let Some(next) = self.continuations.get(&command_string[0]) {
             return next.has_command(&command_string[1..]);
         }
-        return (false, false);
+        return (false, None);
     }
 }
 
 #[derive(Component)]
 pub struct Selected {}
 
+/// How many actions can be undone
+pub const HISTORY_LIMIT: usize = 200;
+
+/// A part before and after an action, None when it didn't exist
+#[derive(Debug, Clone)]
+pub struct PartChange {
+    pub entity: Entity,
+    pub before: Option<Part>,
+    pub after: Option<Part>,
+}
+
+#[derive(Debug, Clone)]
 pub struct Action {
-    affected_entities: Vec<u64>,
-    
+    pub changes: Vec<PartChange>,
+}
 
-    
-    //change: Change,
-} 
+impl Action {
+    /// Points every change on `from` at `to`, for when undoing respawns a part as a new entity
+    pub fn remap_entity(&mut self, from: Entity, to: Entity) {
+        for change in &mut self.changes {
+            if change.entity == from {
+                change.entity = to;
+            }
+        }
+    }
+}
+
+fn part_snapshot(world: &mut World) -> HashMap<Entity, Part> {
+    let mut query = world.query::<(Entity, &BasePart, Option<&AdjustableHull>, Option<&Turret>)>();
+    return query.iter(world).map(|part| (part.0, Part::from_optionals((part.1, part.2, part.3)))).collect();
+}
+
+fn snapshot_changes(before: &HashMap<Entity, Part>, mut after: HashMap<Entity, Part>) -> Vec<PartChange> {
+    let mut changes = Vec::new();
+    for (entity, before_part) in before {
+        let after_part = after.remove(entity);
+        if after_part.as_ref() != Some(before_part) {
+            changes.push(PartChange { entity: *entity, before: Some(before_part.clone()), after: after_part });
+        }
+    }
+    for (entity, after_part) in after {
+        changes.push(PartChange { entity, before: None, after: Some(after_part) });
+    }
+    changes.sort_by_key(|change| change.entity);
+    return changes;
+}
 
 struct QueuedCommand {
     multiplier: f32,
@@ -250,27 +270,29 @@ fn execute_queued_commands(
     world: &mut World,
     //key: Res<ButtonInput<KeyCode>>,
 ){
-    let mut system_state: SystemState<(
-        ResMut<EditorData>,
-        ResMut<CommandData>,
-    )> = SystemState::new(world);
-
-    let (mut editor_data,mut command_data) = system_state.get_mut(world);
+    let mut queued_actions = std::mem::take(&mut world.resource_mut::<EditorData>().queued_actions);
 
+    while let Some(action) = queued_actions.pop_front() {
+        if !action.changes_parts() {
+            world.trigger(action);
+            continue;
+        }
 
-    // let mut flip_floating = false;
-    //
-    // if flip_floating {
-    //     editor_data.floating=!editor_data.floating;
-    // }
-
-
-    //for command in editor_data.queued_actions.into_iter() {
-
-    let mut queued_actions = std::mem::take(&mut editor_data.queued_actions);
-
-    while(queued_actions.len()>0){
-        world.trigger(queued_actions.pop_front().unwrap());
+        //snapshot every part around the action so it can be undone
+        let before = part_snapshot(world);
+        world.trigger(action);
+        world.flush();
+        let mut changes = snapshot_changes(&before, part_snapshot(world));
+        if changes.is_empty() { continue; }
+        mirror_changes(world, &changes);
+        changes = snapshot_changes(&before, part_snapshot(world));
+
+        let mut editor_data = world.resource_mut::<EditorData>();
+        editor_data.undone_actions.clear();
+        editor_data.action_history.push(Action { changes });
+        if editor_data.action_history.len() > HISTORY_LIMIT {
+            editor_data.action_history.remove(0);
+        }
     }
 }
 
@@ -306,7 +328,8 @@ pub fn translate_floatings(
                     }
                 }
             }
-            return true;
+            //only parts, not the water plane
+            return base_part_mesh_query.contains(entity);
         },
         ..default()
     }).first() else {
@@ -367,6 +390,7 @@ fn command_typing(
     mut editor_options: ResMut<EditorOptions>,
     input_events: Res<Events<KeyboardInput>>,
     input_reader: Local<EventCursor<KeyboardInput>>,
+    key: Res<ButtonInput<KeyCode>>,
     mut contexts: EguiContexts,
 ){
 
@@ -376,10 +400,23 @@ fn command_typing(
     });
     if focused {return;}
 
+    let ctrl_pressed = key.pressed(KeyCode::ControlLeft) || key.pressed(KeyCode::ControlRight);
+
     for input in input_reader.clone().read(&input_events) {
         if !input.state.is_pressed() {
             continue;
         };
+        //chords aren't typed into the command so the keymap can't hold them, see the keymap docs
+        if ctrl_pressed {
+            if let Key::Character(smol_str) = &input.logical_key {
+                match smol_str.to_lowercase().as_str() {
+                    "s" => {editor_data.queued_actions.push_front(EditorActionEvent::Save { path: None });},
+                    "r" => {editor_data.queued_actions.push_front(EditorActionEvent::Redo { count: 1 });},
+                    _ => {}
+                }
+            }
+            continue;
+        }
         let char: Option<u8> = match &input.logical_key {
             Key::Character(smol_str) => {
                 if smol_str.len() > 1 {
@@ -393,7 +430,21 @@ fn command_typing(
             Key::Space => {
                 Some(" ".as_bytes()[0])
             },
-            //Key::ArrowDown => todo!(),
+            Key::ArrowDown => {
+                //newer ex lines, past the newest is an empty line
+                if let Some(index) = command_data.ex_history_index {
+                    if index+1 < command_data.ex_history.len() {
+                        command_data.ex_history_index = Some(index+1);
+                        let line = format!(":{}",command_data.ex_history[index+1]);
+                        command_data.current_command = line.into_bytes();
+                    } else {
+                        command_data.ex_history_index = None;
+                        command_data.current_command = b":".to_vec();
+                    }
+                    command_data.current_byte_index = command_data.current_command.len();
+                }
+                None
+            },
             Key::ArrowLeft => {
                 if command_data.current_byte_index != 0 {
                     command_data.current_byte_index-=1;
@@ -406,7 +457,43 @@ fn command_typing(
                 }
                 None
             },
-            //Key::ArrowUp => todo!(),
+            Key::ArrowUp => {
+                //older ex lines
+                let in_ex = command_data.current_command.is_empty() || command_data.current_command[0] == b':';
+                if in_ex && !command_data.ex_history.is_empty() {
+                    let index = match command_data.ex_history_index {
+                        Some(index) => index.saturating_sub(1),
+                        None => command_data.ex_history.len()-1,
+                    };
+                    command_data.ex_history_index = Some(index);
+                    let line = format!(":{}",command_data.ex_history[index]);
+                    command_data.current_command = line.into_bytes();
+                    command_data.current_byte_index = command_data.current_command.len();
+                }
+                None
+            },
+            Key::Tab => {
+                if command_data.current_command.first() == Some(&b':') {
+                    let line = String::from_utf8(command_data.current_command[1..].to_vec()).unwrap();
+                    command_data.current_command = format!(":{}",complete(&line)).into_bytes();
+                    command_data.current_byte_index = command_data.current_command.len();
+                }
+                None
+            },
+            Key::Enter => {
+                if command_data.current_command.first() == Some(&b':') {
+                    let line = String::from_utf8(command_data.current_command[1..].to_vec()).unwrap();
+                    if command_data.ex_history.last() != Some(&line) {
+                        command_data.ex_history.push(line.clone());
+                        if command_data.ex_history.len() > 100 {
+                            command_data.ex_history.remove(0);
+                        }
+                    }
+                    command_data.ex_history_index = None;
+                    run_typed(&format!(":{}",line), 0, &mut command_data, &mut editor_data, &mut editor_options);
+                }
+                None
+            },
             Key::Escape => {
                 if command_data.current_command.is_empty() {
                     command_data.mode = CommandMode::Translation;
@@ -440,100 +527,207 @@ fn command_typing(
         command_data.current_command.insert(index, char); 
         command_data.current_byte_index+=1;
 
+        //ex lines only run on enter
+        if command_data.current_command[0] == b':' {
+            continue;
+        }
+
         let string = String::from_utf8(command_data.current_command.clone()).unwrap();
+        if run_typed(&string, 0, &mut command_data, &mut editor_data, &mut editor_options) {
+            command_data.current_byte_index=0;
+            command_data.current_command.clear();
+        }
+    }
+}
 
-        let regex: Regex = Regex::new(r"^(\d*(\.\d*)?)?([a-zA-Z ]+)?$").unwrap();
-        if regex.is_match(&string) {
-            let captures = regex.captures(&string).unwrap();
-            let num = captures.get(1);
-            let command = captures.get(3);
-            if let Some(command_match) = command {
-                let mut mult: f32 = 1.0;
-                if let Some(num_match) = num{
-                    if let Ok(num) = num_match.as_str().parse::<f32>() {
-                        mult = num;
-                    }
-                }
+/// Runs typed text if it's a whole command, returns false if it's still being typed.
+/// depth is how many macros deep the text is being replayed, only typed text is recorded
+fn run_typed(
+    text: &str,
+    depth: usize,
+    command_data: &mut CommandData,
+    editor_data: &mut EditorData,
+    editor_options: &mut EditorOptions,
+) -> bool {
+    let parsed = if let Some(line) = text.strip_prefix(':') {
+        Parse::Complete(ParsedCommand::Ex { line: line.to_owned() })
+    } else {
+        let recording = depth == 0 && command_data.macros.recording.is_some();
+        match parse_macro_command(text, &command_data.keymap, recording) {
+            MacroParse::NotMacro => {},
+            MacroParse::Incomplete => return false,
+            MacroParse::Invalid => return true,
+            MacroParse::Complete(macro_command) => {
+                run_macro_command(macro_command, text, depth, command_data, editor_data, editor_options);
+                return true;
+            },
+        }
 
-                let is_command = command_data.commands[command_data.mode].has_command(command_match.as_str().as_bytes());
-                
-                if is_command.0 {
-                    if is_command.1 {
-                        match command_data.mode {
-                            CommandMode::Translation => match command_match.as_str() {
-                                "W" => {editor_data.queued_actions.push_front(EditorActionEvent::MoveRelativeDir { vector: Vec3::NEG_Z, mult: mult });},
-                                "A" => {editor_data.queued_actions.push_front(EditorActionEvent::MoveRelativeDir { vector: Vec3::NEG_X, mult: mult });},
-                                "S" => {editor_data.queued_actions.push_front(EditorActionEvent::MoveRelativeDir { vector: Vec3::Z, mult: mult });},
-                                "D" => {editor_data.queued_actions.push_front(EditorActionEvent::MoveRelativeDir { vector: Vec3::X, mult: mult });},
-                                "Q" => {editor_data.queued_actions.push_front(EditorActionEvent::MoveRelativeDir { vector: Vec3::NEG_Y, mult: mult });},
-                                "E" => {editor_data.queued_actions.push_front(EditorActionEvent::MoveRelativeDir { vector: Vec3::Y, mult: mult });},
-                                //"W" => {editor_command_writer.send(EditorCommandEvent::MoveRelativeDir { vector: *Dir3::NEG_Z, mult: mult });},
-
-
-
-                                "w" => {editor_data.queued_actions.push_front(EditorActionEvent::SmartMoveRelativeDir { dir: Dir3::NEG_Z, mult: mult });},
-                                "a" => {editor_data.queued_actions.push_front(EditorActionEvent::SmartMoveRelativeDir { dir: Dir3::NEG_X, mult: mult });},
-                                "s" => {editor_data.queued_actions.push_front(EditorActionEvent::SmartMoveRelativeDir { dir: Dir3::Z, mult: mult });},
-                                "d" => {editor_data.queued_actions.push_front(EditorActionEvent::SmartMoveRelativeDir { dir: Dir3::X, mult: mult });},
-                                "q" => {editor_data.queued_actions.push_front(EditorActionEvent::SmartMoveRelativeDir { dir: Dir3::NEG_Y, mult: mult });},
-                                "e" => {editor_data.queued_actions.push_front(EditorActionEvent::SmartMoveRelativeDir { dir: Dir3::Y, mult: mult });},
-
-                                "y" => {editor_data.queued_actions.push_front(EditorActionEvent::Copy {});},
-                                "p" => {editor_data.queued_actions.push_front(EditorActionEvent::Paste { selected: true });},
-
-                                "f" => {command_data.mode = CommandMode::Attributes}
-
-                                //"F" => {editor_data.queued_actions.push_front(EditorActionEvent::SetEditorSetting { change: EditorSettingChange { floating: Some(), ..default()} });}
-                                "F" => {editor_options.floating = !editor_options.floating;}
-                                _ => {}
-                            },
-                            CommandMode::Attributes => match command_match.as_str() {
-                                "w" => {editor_data.queued_actions.push_front(EditorActionEvent::SwitchSelectedAttribute{offset:-1,do_loop:false});},
-                                "s" => {editor_data.queued_actions.push_front(EditorActionEvent::SwitchSelectedAttribute{offset:1 ,do_loop:false});},
-                                "a" => {editor_data.queued_actions.push_front(EditorActionEvent::SwitchSelectedAttribute{offset:-5,do_loop:false});},
-                                "d" => {editor_data.queued_actions.push_front(EditorActionEvent::SwitchSelectedAttribute{offset:5 ,do_loop:false});},
-
-                                " " => {editor_data.queued_actions.push_front(EditorActionEvent::SetAttribute {attribute: None, value: mult.to_string()});},
-
-                                "L" => {
-                                    let orig_lang = editor_data.language.clone();
-                                    editor_data.queued_actions.push_front(EditorActionEvent::SetEditorSetting {
-                                        change: EditorSettingChange {language: Some(
-                                                    Language::VARIANTS[((Language::VARIANTS.iter().position(|x| *x==orig_lang).unwrap()+1)%Language::SIZE)]
-                                                ), ..default() }
-                                    });
-                                },
-                                _ => {}
-                            },
-                            CommandMode::Rotation => todo!(),
-                            CommandMode::Disabled => todo!(),
-                        }
+        //. repeats the last command that changed parts
+        if text == "." {
+            match &command_data.last_command {
+                Some(last_command) => Parse::Complete(last_command.clone()),
+                None => Parse::Invalid,
+            }
+        } else {
+            parse_command(text, command_data.mode, &command_data.commands[command_data.mode], &command_data.keymap)
+        }
+    };
 
-        
+    match parsed {
+        Parse::Incomplete => return false,
+        Parse::Invalid => return true,
+        Parse::Complete(parsed_command) => {
+            if depth == 0 {
+                if let Some(recording) = &mut command_data.macros.recording {
+                    recording.1.push(text.to_owned());
+                }
+            }
+            finish_command(parsed_command, command_data, editor_data, editor_options);
+            return true;
+        },
+    }
+}
 
-                        let mut history: String= String::new();
-                        if mult!=1.0 {
-                            history.push_str(&mult.to_string());
-                        }
-                        history.push_str(&command_match.as_str());
-                        command_data.command_history.push_front(history);
-                        command_data.command_history.truncate(100);
-                        
-                        command_data.current_byte_index=0;
-                        command_data.current_command.clear();
+fn run_macro_command(
+    macro_command: MacroCommand,
+    text: &str,
+    depth: usize,
+    command_data: &mut CommandData,
+    editor_data: &mut EditorData,
+    editor_options: &mut EditorOptions,
+){
+    match macro_command {
+        MacroCommand::Record(register) => {
+            command_data.macros.recording = Some((register, Vec::new()));
+        },
+        MacroCommand::StopRecording => {
+            command_data.macros.stop_recording();
+        },
+        MacroCommand::Replay { register, count } => {
+            if depth >= MACRO_DEPTH_LIMIT {
+                println!("@{} replays macros more than {} deep, stopping",register,MACRO_DEPTH_LIMIT);
+                return;
+            }
+            //the replay gets recorded instead of what it runs
+            if depth == 0 {
+                if let Some(recording) = &mut command_data.macros.recording {
+                    recording.1.push(text.to_owned());
+                }
+            }
+            let Some(commands) = command_data.macros.registers.get(&register).cloned() else {
+                println!("@{} is empty",register);
+                return;
+            };
+            for _ in 0..count {
+                for command in &commands {
+                    if !run_typed(command, depth+1, command_data, editor_data, editor_options) {
+                        println!("{:?} in @{} isn't a whole command",command,register);
                     }
-                }else{
-                    command_data.current_byte_index=0;
-                    command_data.current_command.clear();
                 }
             }
-        }else{
-            command_data.current_byte_index=0;
-            command_data.current_command.clear();
-        }
+        },
     }
 }
 
+/// Runs a command, records it in the history and clears the command bar
+fn finish_command(
+    parsed_command: ParsedCommand,
+    command_data: &mut CommandData,
+    editor_data: &mut EditorData,
+    editor_options: &mut EditorOptions,
+){
+    let queued = editor_data.queued_actions.len();
+    run_command(&parsed_command, command_data, editor_data, editor_options);
+    if editor_data.queued_actions.len() > queued && editor_data.queued_actions.front().unwrap().changes_parts() {
+        command_data.last_command = Some(parsed_command.clone());
+    }
+
+    command_data.command_history.push_front(parsed_command);
+    command_data.command_history.truncate(100);
+
+    command_data.current_byte_index=0;
+    command_data.current_command.clear();
+}
+
+/// Queues the actions for a parsed command
+fn run_command(
+    parsed_command: &ParsedCommand,
+    command_data: &mut CommandData,
+    editor_data: &mut EditorData,
+    editor_options: &mut EditorOptions,
+){
+    let (count, action, register) = match parsed_command {
+        ParsedCommand::Operator { operator, count, target, register } => {
+            editor_data.queued_actions.push_front(EditorActionEvent::Operate { operator: *operator, target: *target, count: *count, register: *register });
+            return;
+        },
+        ParsedCommand::Ex { line } => {
+            if let Err(error) = run_ex(line, editor_data, editor_options) {
+                println!(":{} failed: {}",line,error);
+            }
+            return;
+        },
+        ParsedCommand::Simple { count, action, register, .. } => (count, action, register),
+    };
+    let mult: f32 = count.unwrap_or(1.0);
+    //rotations without a count turn a quarter
+    let degrees: f32 = count.unwrap_or(90.0);
+
+    let action = match action {
+        KeyAction::MoveForward => EditorActionEvent::MoveRelativeDir { vector: Vec3::NEG_Z, mult: mult },
+        KeyAction::MoveLeft => EditorActionEvent::MoveRelativeDir { vector: Vec3::NEG_X, mult: mult },
+        KeyAction::MoveBack => EditorActionEvent::MoveRelativeDir { vector: Vec3::Z, mult: mult },
+        KeyAction::MoveRight => EditorActionEvent::MoveRelativeDir { vector: Vec3::X, mult: mult },
+        KeyAction::MoveDown => EditorActionEvent::MoveRelativeDir { vector: Vec3::NEG_Y, mult: mult },
+        KeyAction::MoveUp => EditorActionEvent::MoveRelativeDir { vector: Vec3::Y, mult: mult },
+
+        KeyAction::SmartMoveForward => EditorActionEvent::SmartMoveRelativeDir { dir: Dir3::NEG_Z, mult: mult },
+        KeyAction::SmartMoveLeft => EditorActionEvent::SmartMoveRelativeDir { dir: Dir3::NEG_X, mult: mult },
+        KeyAction::SmartMoveBack => EditorActionEvent::SmartMoveRelativeDir { dir: Dir3::Z, mult: mult },
+        KeyAction::SmartMoveRight => EditorActionEvent::SmartMoveRelativeDir { dir: Dir3::X, mult: mult },
+        KeyAction::SmartMoveDown => EditorActionEvent::SmartMoveRelativeDir { dir: Dir3::NEG_Y, mult: mult },
+        KeyAction::SmartMoveUp => EditorActionEvent::SmartMoveRelativeDir { dir: Dir3::Y, mult: mult },
+
+        //plain keys turn around the camera's axes, local ones around the part's own
+        KeyAction::PitchForward => EditorActionEvent::RotateRelativeDir { axis: Vec3::X, degrees: -degrees, local: false },
+        KeyAction::PitchBack => EditorActionEvent::RotateRelativeDir { axis: Vec3::X, degrees: degrees, local: false },
+        KeyAction::YawLeft => EditorActionEvent::RotateRelativeDir { axis: Vec3::Y, degrees: degrees, local: false },
+        KeyAction::YawRight => EditorActionEvent::RotateRelativeDir { axis: Vec3::Y, degrees: -degrees, local: false },
+        KeyAction::RollLeft => EditorActionEvent::RotateRelativeDir { axis: Vec3::Z, degrees: degrees, local: false },
+        KeyAction::RollRight => EditorActionEvent::RotateRelativeDir { axis: Vec3::Z, degrees: -degrees, local: false },
+        KeyAction::LocalPitchForward => EditorActionEvent::RotateRelativeDir { axis: Vec3::X, degrees: -degrees, local: true },
+        KeyAction::LocalPitchBack => EditorActionEvent::RotateRelativeDir { axis: Vec3::X, degrees: degrees, local: true },
+        KeyAction::LocalYawLeft => EditorActionEvent::RotateRelativeDir { axis: Vec3::Y, degrees: degrees, local: true },
+        KeyAction::LocalYawRight => EditorActionEvent::RotateRelativeDir { axis: Vec3::Y, degrees: -degrees, local: true },
+        KeyAction::LocalRollLeft => EditorActionEvent::RotateRelativeDir { axis: Vec3::Z, degrees: degrees, local: true },
+        KeyAction::LocalRollRight => EditorActionEvent::RotateRelativeDir { axis: Vec3::Z, degrees: -degrees, local: true },
+
+        KeyAction::PreviousAttribute => EditorActionEvent::SwitchSelectedAttribute{offset:-1,do_loop:false},
+        KeyAction::NextAttribute => EditorActionEvent::SwitchSelectedAttribute{offset:1 ,do_loop:false},
+        KeyAction::PreviousAttributeColumn => EditorActionEvent::SwitchSelectedAttribute{offset:-5,do_loop:false},
+        KeyAction::NextAttributeColumn => EditorActionEvent::SwitchSelectedAttribute{offset:5 ,do_loop:false},
+        KeyAction::SetAttribute => EditorActionEvent::SetAttribute {attribute: None, value: mult.to_string()},
+
+        KeyAction::Paste => EditorActionEvent::Paste { selected: true, register: *register },
+        KeyAction::Mirror => EditorActionEvent::Mirror { plane: MirrorPlane::default(), keep_original: true },
+        KeyAction::Undo => EditorActionEvent::Undo { count: mult.max(1.0) as usize },
+        KeyAction::CycleLanguage => {
+            let orig_lang = editor_data.language.clone();
+            EditorActionEvent::SetEditorSetting {
+                change: EditorSettingChange {language: Some(
+                            Language::VARIANTS[((Language::VARIANTS.iter().position(|x| *x==orig_lang).unwrap()+1)%Language::SIZE)]
+                        ), ..default() }
+            }
+        },
+
+        KeyAction::ToggleFloating => {editor_options.floating = !editor_options.floating; return;},
+        KeyAction::AttributesMode => {command_data.mode = CommandMode::Attributes; return;},
+        KeyAction::RotationMode => {command_data.mode = CommandMode::Rotation; return;},
+    };
+    editor_data.queued_actions.push_front(action);
+}
+
 pub fn on_gizmo_update(
     // mut changed_gizmo_parts: Query<(&Transform, &mut BasePart, Entity), Changed<GizmoTarget>>,
     // gizmo_targets: Query<&GizmoTarget>,
@@ -665,6 +859,7 @@ pub fn on_part_changed(
 
 pub fn on_click(
     click: Trigger<Pointer<Down>>,
+    editor_data: Res<EditorData>,
     base_part_query: Query<&BasePartMesh>,
     selected: Query<Entity, With<Selected>>,
     parent_query: Query<&Parent>,
@@ -676,6 +871,10 @@ pub fn on_click(
     if click.event().button != PointerButton::Primary {
         return;
     }
+    //that click places parts instead
+    if editor_data.placing.is_some() {
+        return;
+    }
     if !gizmo_targets.iter().all(|target| !target.is_focused() && !target.is_active()) {
         return;
     }
@@ -1,10 +1,11 @@
 use core::f32;
-use std::ops::Deref;
+use std::{ops::Deref, path::Path};
 
-use bevy::{app::App, asset::{AssetServer, Assets}, color::Color, ecs::{event::Event, system::Commands}, math::{Dir3, EulerRot, Isometry3d, Quat, Vec3}, pbr::StandardMaterial, picking::mesh_picking::ray_cast::{MeshRayCast, RayCastSettings}, prelude::{Camera, Entity, Gizmos, GlobalTransform, Query, Res, ResMut, Single, Transform, Trigger, With}, render::mesh::Mesh, state::commands, utils::HashMap, window::Window};
+use bevy::{app::App, asset::{AssetServer, Assets}, color::Color, ecs::{event::Event, system::{Commands, EntityCommands}}, hierarchy::DespawnRecursiveExt, math::{Dir3, EulerRot, Isometry3d, Quat, Vec3}, pbr::StandardMaterial, picking::mesh_picking::ray_cast::{MeshRayCast, RayCastSettings}, prelude::{Camera, Entity, Gizmos, GlobalTransform, Query, Res, ResMut, Single, Transform, Trigger, With}, render::mesh::Mesh, state::commands, utils::HashMap};
+use bevy_egui::EguiClipboard;
 use enum_collections::Enumerated;
 
-use crate::{cam_movement::EditorCamera, editor::{DebugGizmo, EditorData, EditorOptions, Selected}, editor_ui::{Hovered, Language, PropertiesDisplayData}, editor_utils::{arrow, cuboid_face, cuboid_face_normal, cuboid_scale, get_nearby, round_to_axis, set_adjustable_hull_width, simple_closest_dist, to_touch, with_corner_adjacent_adjustable_hulls, AdjHullSide}, parsing::{AdjustableHull, BasePart, Part, Turret}, parts::{base_part_to_bevy_transform, bevy_quat_to_unity, bevy_to_unity_translation, get_collider, place_part, unity_to_bevy_quat, unity_to_bevy_translation, PartAttributes, PartRegistry}};
+use crate::{cli::ShipFormat, cam_movement::EditorCamera, command_grammar::{Operator, Target}, editor::{DebugGizmo, EditorData, EditorOptions, Selected}, editor_ui::{DiffOverlay, Language, PropertiesDisplayData}, interchange::{load_parts, save_parts}, editor_utils::{adjacent_adjustable_hulls, arrow, cuboid_face, cuboid_face_normal, cuboid_scale, get_nearby, round_to_axis, set_adjustable_hull_width, simple_closest_dist, with_corner_adjacent_adjustable_hulls, AdjHullSide}, parsing::{save_ship, AdjustableHull, BasePart, Part, Ship, ShipHeader, Turret}, mirror::{mirror_part, MirrorPlane}, placement::Placement, parts::{base_part_to_bevy_transform, bevy_quat_to_unity, bevy_to_unity_translation, get_collider, place_part, unity_to_bevy_quat, unity_to_bevy_translation, PartAttributes, PartRegistry}, InitData};
 
 
 #[derive(Event)]
@@ -15,8 +16,38 @@ pub enum EditorActionEvent {
     SetAttribute {attribute: Option<PartAttributes>, value: String},
     SetEditorSetting {change: EditorSettingChange},
     SpawnNewPart {part_id: i32, selected: bool, part: Option<Part>},
-    Copy {},
-    Paste {selected: bool},
+    Copy {register: Option<char>},
+    Paste {selected: bool, register: Option<char>},
+    Place {},
+    Save {path: Option<String>},
+    ExportParts {path: String},
+    ImportParts {path: String, selected: bool},
+    CompareWith {path: Option<String>},
+    Undo {count: usize},
+    Redo {count: usize},
+    RotateRelativeDir {axis: Vec3, degrees: f32, local: bool},
+    Operate {operator: Operator, target: Target, count: usize, register: Option<char>},
+    Open {path: String},
+    Select {attribute: PartAttributes, value: String},
+    //duplicates the selection on the other side, or moves it there if not keep_original
+    Mirror {plane: MirrorPlane, keep_original: bool},
+}
+
+impl EditorActionEvent {
+    /// Whether the action can change parts, only those are recorded in the undo history
+    pub fn changes_parts(&self) -> bool {
+        match self {
+            EditorActionEvent::MoveRelativeDir {..} => true,
+            EditorActionEvent::SmartMoveRelativeDir {..} => true,
+            EditorActionEvent::SetAttribute {..} => true,
+            EditorActionEvent::Place {..} => true,
+            EditorActionEvent::Mirror {..} => true,
+            EditorActionEvent::ImportParts {..} => true,
+            EditorActionEvent::RotateRelativeDir {..} => true,
+            EditorActionEvent::Operate {operator, ..} => *operator != Operator::Yank,
+            _ => false,
+        }
+    }
 }
 
 pub fn add_actions(app: &mut App) {
@@ -28,6 +59,17 @@ pub fn add_actions(app: &mut App) {
     app.add_observer(spawn_new_part);
     app.add_observer(copy);
     app.add_observer(paste);
+    app.add_observer(place);
+    app.add_observer(mirror_selected);
+    app.add_observer(save);
+    app.add_observer(export_parts);
+    app.add_observer(import_parts);
+    app.add_observer(compare_with);
+    app.add_observer(undo_redo);
+    app.add_observer(rotate_selected_relative_dir);
+    app.add_observer(operate);
+    app.add_observer(open_ship);
+    app.add_observer(select_where);
 }
 
 pub fn modify_selected_attribute(
@@ -68,7 +110,8 @@ pub fn modify_selected_attribute(
                 }
 
                 let rotation = unity_to_bevy_quat(&rotation_vector);
-                let origin = unity_to_bevy_translation(&all_parts.get(latest_selected).unwrap().0.position);
+                let Ok(latest_part) = all_parts.get(latest_selected) else {return;};
+                let origin = unity_to_bevy_translation(&latest_part.0.position);
 
 
 
@@ -188,6 +231,61 @@ pub fn move_selected_relative_dir(
 }
 
 
+/// Rotates the selection around the latest selected part, or the median of the selection if there isn't one
+pub fn rotate_selected_relative_dir(
+    trigger: Trigger<EditorActionEvent>,
+    editor_data: Res<EditorData>,
+    selected: Query<Entity, With<Selected>>,
+    mut all_parts: Query<&mut BasePart>,
+    camera_transform: Single<(&Camera, &GlobalTransform, &EditorCamera)>,
+){
+    let EditorActionEvent::RotateRelativeDir{axis, degrees, local} = trigger.event() else {return;};
+    if selected.is_empty() { return; }
+
+    let pivot_entity = editor_data.latest_selected.filter(|entity| selected.contains(*entity));
+
+    let origin = if let Some(pivot_entity) = pivot_entity {
+        unity_to_bevy_translation(&all_parts.get(pivot_entity).unwrap().position)
+    } else {
+        let mut xs: Vec<f32> = Vec::new();
+        let mut ys: Vec<f32> = Vec::new();
+        let mut zs: Vec<f32> = Vec::new();
+        for selected_entity in &selected {
+            let position = unity_to_bevy_translation(&all_parts.get(selected_entity).unwrap().position);
+            xs.push(position.x);
+            ys.push(position.y);
+            zs.push(position.z);
+        }
+        let median = |values: &mut Vec<f32>| -> f32 {
+            values.sort_by(|a, b| a.total_cmp(b));
+            return values[values.len()/2];
+        };
+        Vec3::new(median(&mut xs), median(&mut ys), median(&mut zs))
+    };
+
+    let world_axis = if *local {
+        //the whole selection turns around the pivot part's axes so it stays in one piece
+        let pivot_entity = pivot_entity.unwrap_or(selected.iter().next().unwrap());
+        unity_to_bevy_quat(&all_parts.get(pivot_entity).unwrap().rotation).mul_vec3(*axis)
+    } else {
+        let mut rot = camera_transform.1.rotation().to_euler(EulerRot::XYZ);
+        rot.0 = (rot.0/f32::consts::FRAC_PI_2).round()*f32::consts::FRAC_PI_2;
+        rot.1 = (rot.1/f32::consts::FRAC_PI_2).round()*f32::consts::FRAC_PI_2;
+        rot.2 = (rot.2/f32::consts::FRAC_PI_2).round()*f32::consts::FRAC_PI_2;
+        Quat::from_euler(EulerRot::XYZ, rot.0, rot.1, rot.2).mul_vec3(*axis)
+    };
+    let rotation = Quat::from_axis_angle(world_axis.normalize(), degrees.to_radians());
+
+    for selected_entity in &selected {
+        let mut selected_part = all_parts.get_mut(selected_entity).unwrap();
+        let mut new_transform = base_part_to_bevy_transform(&selected_part);
+        new_transform.rotate_around(origin, rotation);
+        selected_part.position = bevy_to_unity_translation(&new_transform.translation);
+        selected_part.rotation = bevy_quat_to_unity(&new_transform.rotation);
+    }
+}
+
+
 //need to figure out what to do when multiple parts
 
 pub fn smart_move_selected_relative_dir(
@@ -337,6 +435,7 @@ pub fn smart_move_selected_relative_dir(
 pub struct EditorSettingChange {
     pub floating: Option<bool>,
     pub edit_near: Option<bool>,
+    pub symmetry: Option<bool>,
     pub language: Option<Language>,
 }
 
@@ -348,6 +447,7 @@ pub fn set_editor_settings(
     let EditorActionEvent::SetEditorSetting{change} = trigger.event() else {return;};
     if let Some(value) = change.floating { editor_options.floating = value; };
     if let Some(value) = change.edit_near { editor_options.edit_near = value; };
+    if let Some(value) = change.symmetry { editor_options.symmetry = value; };
     if let Some(value) = change.language{ editor_data.language = value; };
     
 }
@@ -355,18 +455,8 @@ pub fn set_editor_settings(
 
 pub fn spawn_new_part(
     trigger: Trigger<EditorActionEvent>,
-    mut editor_options: ResMut<EditorOptions>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    asset_server: Res<AssetServer>,
+    mut editor_data: ResMut<EditorData>,
     part_registry: Res<PartRegistry>,
-    camera_query: Single<(&Camera, &GlobalTransform, &EditorCamera)>,
-    windows: Single<&Window>,
-    hovered: Query<Entity, With<Hovered>>,
-    selected_entities: Query<Entity, With<Selected>>,
-    //mut ray_cast: MeshRayCast,
-    part_query: Query<(&mut BasePart,Option<&AdjustableHull>,&mut Transform)>,
-    mut commands: Commands,
 ){
     let EditorActionEvent::SpawnNewPart{part_id, selected, part} = trigger.event() else {return;};
     let mut part_data;
@@ -385,133 +475,360 @@ pub fn spawn_new_part(
             part_data = Part::Turret(BasePart::default(), Turret { manual_control: false, elevator: None });
             part_data.base_part_mut().id = *part_id;
         }
+        part_data.base_part_mut().armor = part_entry.armor;
     };
 
-    let (camera, camera_transform, _) = *camera_query;
-    let camera_translation = camera_transform.translation();
-    let Some(cursor_position) = windows.cursor_position() else { return; };
-    // let Ok(ray) = camera.viewport_to_world(camera_transform, cursor_position) else {
-    //     return;
-    // };
-    let mut dist:f32 = 100.0;
-    // let dir = Dir3::new_unchecked((ray.direction).normalize());
-    let dir = camera_transform.forward();
-
-    //let Some((hit_entity, hit)) = ray_cast.cast_ray(ray, &RayCastSettings::default()).first() else {return;};
-    
-    if let Ok(hovered_entity) = hovered.get_single() {
-        if let Ok(hit_entity_components) = part_query.get(hovered_entity) {
-            let a_adj_hull = if let Part::AdjustableHull(_,adj_hull) = part_data { Some(adj_hull) }else{None};
-            let mut a = get_collider(part_data.base_part(), a_adj_hull.as_ref(), part_registry.parts.get(part_id).unwrap());
-            a.translation=camera_translation+part_registry.parts.get(part_id).unwrap().center;
+    //placed on whatever's under the cursor when clicked
+    let mut placement = Placement::new(vec![part_data], *selected);
+    placement.floating = true;
+    editor_data.placing = Some(placement);
+}
 
-            let b = get_collider(hit_entity_components.0, hit_entity_components.1, part_registry.parts.get(&hit_entity_components.0.id).unwrap());
+pub fn copy(
+    trigger: Trigger<EditorActionEvent>,
+    mut editor_data: ResMut<EditorData>,
+    mut clipboard: ResMut<EguiClipboard>,
+    selected_parts: Query<(&BasePart, Option<&AdjustableHull>, Option<&Turret>), With<Selected>>,
+){
+    let EditorActionEvent::Copy{register} = trigger.event() else {return;};
 
+    let parts: Vec<Part> = selected_parts.iter().map(|selected_part| Part::from_optionals(selected_part)).collect();
+    editor_data.registers.yank(*register, parts, &mut clipboard);
+}
 
-            dist=dist.min(to_touch(&a, &b, dir/* , &mut gizmo */));
-        }
-    }
 
-    let camera_translation = camera_transform.translation();
-    part_data.base_part_mut().position=bevy_to_unity_translation(&(camera_translation+((*dir)*dist)));
-    
-    if *selected {
+pub fn paste(
+    trigger: Trigger<EditorActionEvent>,
+    mut editor_data: ResMut<EditorData>,
+    mut clipboard: ResMut<EguiClipboard>,
+){
+    let EditorActionEvent::Paste{selected, register} = trigger.event() else {return;};
+
+    let Some(to_pastes) = editor_data.registers.get(*register, &mut clipboard) else {
+        println!("nothing to paste in {}", register.map_or("the yank ring".to_owned(), |register| format!("register \"{}", register)));
+        return;
+    };
+
+    editor_data.placing = Some(Placement::new(to_pastes, *selected));
+}
+
+/// Spawns the parts being placed where the ghost is
+pub fn place(
+    trigger: Trigger<EditorActionEvent>,
+    mut editor_data: ResMut<EditorData>,
+    mut editor_options: ResMut<EditorOptions>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    asset_server: Res<AssetServer>,
+    part_registry: Res<PartRegistry>,
+    selected_entities: Query<Entity, With<Selected>>,
+    mut commands: Commands,
+){
+    let EditorActionEvent::Place{} = trigger.event() else {return;};
+    let Some(placement) = editor_data.placing.take() else {return;};
+
+    if placement.selected {
         for selected_entity in &selected_entities {
             commands.entity(selected_entity).remove::<Selected>();
         }
     }
-    
-
 
-    let mut placed_part = commands.spawn_empty();
-    
-    place_part(
-        &mut meshes,
-        &mut materials,
-        &asset_server,
-        &part_registry,
-        &mut placed_part,
-        &part_data,
-    );
-    
+    for to_place in &placement.placed {
+        let mut placed_part = commands.spawn_empty();
+        place_part(
+            &mut meshes,
+            &mut materials,
+            &asset_server,
+            &part_registry,
+            &mut placed_part,
+            to_place,
+        );
 
-    if *selected {
-        placed_part.insert(Selected{});
+        if placement.selected {
+            placed_part.insert(Selected{});
+        }
+    }
+    if placement.selected && placement.floating {
         editor_options.floating = true;
     }
+}
 
 
+/// The parts an operator's target covers, the selection is where every target starts from
+fn resolve_target(
+    target: &Target,
+    count: usize,
+    selected: &Vec<Entity>,
+    parts: &Vec<(Entity, Transform, Option<AdjustableHull>, i32)>,
+    camera_rotation: Quat,
+) -> Vec<Entity> {
+    let index_of = |entity: &Entity| parts.iter().position(|part| part.0 == *entity);
+    match target {
+        Target::Selection => {
+            return selected.clone();
+        },
+        Target::SameId => {
+            let ids: Vec<i32> = selected.iter().filter_map(|entity| index_of(entity)).map(|index| parts[index].3).collect();
+            return parts.iter().filter(|part| ids.contains(&part.3)).map(|part| part.0).collect();
+        },
+        Target::Component => {
+            let mut in_component: Vec<bool> = parts.iter().map(|part| selected.contains(&part.0)).collect();
+            let mut to_visit: Vec<usize> = selected.iter().filter_map(|entity| index_of(entity)).collect();
+            while let Some(index) = to_visit.pop() {
+                for check_index in 0..parts.len() {
+                    if in_component[check_index] { continue; }
+                    //touching or close enough that the game would count it
+                    if simple_closest_dist(&parts[index].1, &parts[check_index].1) <= 0.01 {
+                        in_component[check_index] = true;
+                        to_visit.push(check_index);
+                    }
+                }
+            }
+            return parts.iter().zip(in_component).filter(|pair| pair.1).map(|pair| pair.0.0).collect();
+        },
+        Target::AdjacentHull { dir, .. } => {
+            let hulls: Vec<(Transform, AdjustableHull)> = parts.iter().filter_map(|part| Some((part.1, part.2?))).collect();
+            let hull_entities: Vec<Entity> = parts.iter().filter(|part| part.2.is_some()).map(|part| part.0).collect();
+            let world_dir = Dir3::new_unchecked(camera_rotation.mul_vec3(**dir).normalize());
+
+            let mut found: Vec<Entity> = Vec::new();
+            for selected_entity in selected {
+                let Some(mut current) = hull_entities.iter().position(|entity| entity == selected_entity) else {continue;};
+                //walk the chain of hulls, the count is how many to go through
+                for _ in 0..count {
+                    let side = round_to_axis(&hulls[current].0, &world_dir);
+                    let adjacents = adjacent_adjustable_hulls((&hulls[current].0, &hulls[current].1), &hulls);
+                    let Some(next) = adjacents.get(&side) else {break;};
+                    current = next.0;
+                    if !found.contains(&hull_entities[current]) && !selected.contains(&hull_entities[current]) {
+                        found.push(hull_entities[current]);
+                    }
+                }
+            }
+            return found;
+        },
+    }
 }
 
-pub fn copy(
+/// Applies a vim style operator to what its target covers
+pub fn operate(
     trigger: Trigger<EditorActionEvent>,
     mut editor_data: ResMut<EditorData>,
-    selected_parts: Query<(&BasePart, Option<&AdjustableHull>, Option<&Turret>), With<Selected>>,
+    mut clipboard: ResMut<EguiClipboard>,
+    selected: Query<Entity, With<Selected>>,
+    mut all_parts: Query<(Entity, &mut BasePart, Option<&AdjustableHull>, Option<&Turret>)>,
+    part_registry: Res<PartRegistry>,
+    camera_transform: Single<(&Camera, &GlobalTransform, &EditorCamera)>,
+    mut commands: Commands,
 ){
-    let EditorActionEvent::Copy{} = trigger.event() else {return;};
+    let EditorActionEvent::Operate{operator, target, count, register} = trigger.event() else {return;};
 
-    editor_data.clipboard.clear();
-    for selected_part in &selected_parts {
-        editor_data.clipboard.push(Part::from_optionals(selected_part));
+    let selected: Vec<Entity> = selected.iter().collect();
+    let mut parts: Vec<(Entity, Transform, Option<AdjustableHull>, i32)> = Vec::new();
+    for (entity, base_part, adjustable_hull, _) in &all_parts {
+        let Some(part_data) = part_registry.parts.get(&base_part.id) else {continue;};
+        parts.push((entity, get_collider(&base_part, adjustable_hull, part_data), adjustable_hull.copied(), base_part.id));
+    }
+    let targets = resolve_target(target, *count, &selected, &parts, camera_transform.1.rotation());
+    if targets.is_empty() {
+        println!("{} {} has nothing to act on", operator, target);
+        return;
+    }
+
+    //like vim, deleting also yanks
+    if *operator == Operator::Yank || *operator == Operator::Delete {
+        let mut yanked: Vec<Part> = Vec::new();
+        for target_entity in &targets {
+            let (_, base_part, adjustable_hull, turret) = all_parts.get(*target_entity).unwrap();
+            yanked.push(Part::from_optionals((&base_part, adjustable_hull, turret)));
+        }
+        editor_data.registers.yank(*register, yanked, &mut clipboard);
+    }
+
+    match operator {
+        Operator::Yank => {},
+        Operator::Delete => {
+            for target_entity in &targets {
+                commands.entity(*target_entity).despawn_recursive();
+            }
+            if editor_data.latest_selected.is_some_and(|latest| targets.contains(&latest)) {
+                editor_data.latest_selected = None;
+            }
+        },
+        Operator::Recolor => {
+            let Some(anchor) = editor_data.latest_selected.and_then(|latest| all_parts.get(latest).ok()) else {
+                println!("recolor needs a latest selected part to take the color from");
+                return;
+            };
+            let color = anchor.1.color;
+            for target_entity in &targets {
+                all_parts.get_mut(*target_entity).unwrap().1.color = color;
+            }
+        },
+        Operator::Align => {
+            let Some(anchor) = editor_data.latest_selected.and_then(|latest| all_parts.get(latest).ok()) else {
+                println!("align needs a latest selected part to line up with");
+                return;
+            };
+            //targets get the anchor's rotation and are moved onto the line through its length
+            let anchor_entity = anchor.0;
+            let anchor_transform = base_part_to_bevy_transform(&anchor.1);
+            let anchor_rotation = anchor.1.rotation;
+            let forward = anchor_transform.forward();
+            for target_entity in &targets {
+                if *target_entity == anchor_entity { continue; }
+                let mut target_part = all_parts.get_mut(*target_entity).unwrap();
+                let position = unity_to_bevy_translation(&target_part.1.position);
+                let along = (position-anchor_transform.translation).dot(*forward);
+                target_part.1.position = bevy_to_unity_translation(&(anchor_transform.translation+(forward*along)));
+                target_part.1.rotation = anchor_rotation;
+            }
+        },
     }
 }
 
+pub fn save(
+    trigger: Trigger<EditorActionEvent>,
+    init_data: Res<InitData>,
+    ship_header: Res<ShipHeader>,
+    all_parts: Query<(Entity, &BasePart, Option<&AdjustableHull>, Option<&Turret>)>,
+){
+    let EditorActionEvent::Save{path} = trigger.event() else {return;};
+    let path = path.clone().unwrap_or(init_data.file_path.clone());
+
+    //sort by entity so parts are written in roughly the order they were loaded
+    let mut entities: Vec<(Entity, Part)> = all_parts.iter().map(|part| {
+        (part.0, Part::from_optionals((part.1, part.2, part.3)))
+    }).collect();
+    entities.sort_by_key(|pair| pair.0);
+    let ship = Ship {
+        header: ship_header.clone(),
+        parts: entities.into_iter().map(|pair| pair.1).collect(),
+    };
 
-pub fn paste(
+    match save_ship(Path::new(&path), &ship) {
+        Ok(()) => println!("saved {:?} parts to {:?}", ship.parts.len(), path),
+        Err(error) => println!("failed to save to {:?}: {:?}", path, error),
+    }
+}
+
+/// Replaces the ship being edited, the undo history doesn't carry over
+pub fn open_ship(
     trigger: Trigger<EditorActionEvent>,
     mut editor_data: ResMut<EditorData>,
+    mut init_data: ResMut<InitData>,
+    mut ship_header: ResMut<ShipHeader>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
     asset_server: Res<AssetServer>,
     part_registry: Res<PartRegistry>,
-    camera_query: Single<(&Camera, &GlobalTransform, &EditorCamera)>,
-    windows: Single<&Window>,
-    hovered: Query<Entity, With<Hovered>>,
-    part_query: Query<(&mut BasePart,Option<&AdjustableHull>,&mut Transform)>,
-    selected_entities: Query<Entity, With<Selected>>,
+    all_parts: Query<Entity, With<BasePart>>,
     mut commands: Commands,
 ){
-    let EditorActionEvent::Paste{selected} = trigger.event() else {return;};
-
-    let mut translation: Vec3 = Vec3::new(0.0,0.0,0.0);
-    for to_paste in &editor_data.clipboard {
-        translation = translation + to_paste.base_part().position;
-    }
-    translation = translation / (editor_data.clipboard.len() as f32);
-
-    let mut dist:f32 = 100.0;
-    //let mut dir = camera_query.1.forward();
+    let EditorActionEvent::Open{path} = trigger.event() else {return;};
 
-    let (camera, camera_transform, _) = *camera_query;
-    let camera_translation = camera_transform.translation();
-
-    let Some(cursor_position) = windows.cursor_position() else { return; };
-    let Ok(ray) = camera.viewport_to_world(camera_transform, cursor_position) else {
-        return;
+    let ship = match ShipFormat::of(Path::new(path)).and_then(|format| format.load(Path::new(path))) {
+        Ok(ship) => ship,
+        Err(error) => {
+            println!("failed to open {:?}: {:?}", path, error);
+            return;
+        }
     };
-    let dir = Dir3::new_unchecked((ray.direction).normalize());
-
-    if editor_data.clipboard.len() == 1 {
-        let single_paste = editor_data.clipboard.first().unwrap();
 
+    for entity in &all_parts {
+        commands.entity(entity).despawn_recursive();
+    }
+    for part in &ship.parts {
+        let mut placed_part = commands.spawn_empty();
+        place_part(
+            &mut meshes,
+            &mut materials,
+            &asset_server,
+            &part_registry,
+            &mut placed_part,
+            part,
+        );
+    }
+    println!("opened {:?} parts from {:?}", ship.parts.len(), path);
 
-        if let Ok(hovered_entity) = hovered.get_single() {
-            if let Ok(hit_entity_components) = part_query.get(hovered_entity) {
-                let thing = single_paste.to_optionals();
-                let mut a = get_collider(thing.0, thing.1, part_registry.parts.get(&single_paste.base_part().id).unwrap());
-                a.translation=camera_translation+part_registry.parts.get(&single_paste.base_part().id).unwrap().center;
+    *ship_header = ship.header;
+    init_data.file_path = path.clone();
+    editor_data.latest_selected = None;
+    editor_data.action_history.clear();
+    editor_data.undone_actions.clear();
+}
 
-                let b = get_collider(hit_entity_components.0, hit_entity_components.1, part_registry.parts.get(&hit_entity_components.0.id).unwrap());
+/// Selects every part whose attribute has the value, numbers only need to be close
+pub fn select_where(
+    trigger: Trigger<EditorActionEvent>,
+    mut editor_data: ResMut<EditorData>,
+    all_parts: Query<(Entity, &BasePart, Option<&AdjustableHull>, Option<&Turret>)>,
+    selected_entities: Query<Entity, With<Selected>>,
+    mut commands: Commands,
+){
+    let EditorActionEvent::Select{attribute, value} = trigger.event() else {return;};
+    let value = value.trim_start_matches('#');
 
-                dist=dist.min(to_touch(&a, &b, dir/* , &mut gizmo */));
-            }
+    for selected_entity in &selected_entities {
+        commands.entity(selected_entity).remove::<Selected>();
+    }
+    editor_data.latest_selected = None;
+
+    let mut count = 0;
+    for (entity, base_part, adjustable_hull, turret) in &all_parts {
+        let Some(field) = attribute.get_field(base_part, adjustable_hull, turret) else {continue;};
+        let matches = match (field.parse::<f32>(), value.parse::<f32>()) {
+            (Ok(field), Ok(value)) => (field-value).abs() < 0.0001,
+            _ => field.trim_start_matches('#').eq_ignore_ascii_case(value),
+        };
+        if matches {
+            commands.entity(entity).insert(Selected{});
+            count += 1;
         }
+    }
+    println!("selected {:?} parts with {:?} {}", count, attribute, value);
+}
 
+pub fn export_parts(
+    trigger: Trigger<EditorActionEvent>,
+    all_parts: Query<(Entity, &BasePart, Option<&AdjustableHull>, Option<&Turret>)>,
+    selected_parts: Query<Entity, With<Selected>>,
+){
+    let EditorActionEvent::ExportParts{path} = trigger.event() else {return;};
+
+    //everything if nothing is selected
+    let mut entities: Vec<(Entity, Part)> = all_parts.iter().filter(|part| {
+        selected_parts.is_empty() || selected_parts.contains(part.0)
+    }).map(|part| {
+        (part.0, Part::from_optionals((part.1, part.2, part.3)))
+    }).collect();
+    entities.sort_by_key(|pair| pair.0);
+    let parts: Vec<Part> = entities.into_iter().map(|pair| pair.1).collect();
+
+    match save_parts(Path::new(path), &parts) {
+        Ok(()) => println!("exported {:?} parts to {:?}", parts.len(), path),
+        Err(error) => println!("failed to export to {:?}: {}", path, error),
     }
+}
 
-    
+pub fn import_parts(
+    trigger: Trigger<EditorActionEvent>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    asset_server: Res<AssetServer>,
+    part_registry: Res<PartRegistry>,
+    selected_entities: Query<Entity, With<Selected>>,
+    mut commands: Commands,
+){
+    let EditorActionEvent::ImportParts{path, selected} = trigger.event() else {return;};
 
-    translation = bevy_to_unity_translation(&(&camera_translation + (dir*dist))) - translation;
+    let parts = match load_parts(Path::new(path)) {
+        Ok(parts) => parts,
+        Err(error) => {
+            println!("failed to import {:?}: {}", path, error);
+            return;
+        }
+    };
 
     if *selected {
         for selected_entity in &selected_entities {
@@ -519,10 +836,11 @@ pub fn paste(
         }
     }
 
-    for to_paste in &editor_data.clipboard {
-        let mut to_place = to_paste.clone();
-        to_place.base_part_mut().position += translation;
-    
+    for part in &parts {
+        if !part_registry.parts.contains_key(&part.base_part().id) {
+            println!("skipping imported part with unknown id {:?}", part.base_part().id);
+            continue;
+        }
         let mut placed_part = commands.spawn_empty();
         place_part(
             &mut meshes,
@@ -530,15 +848,158 @@ pub fn paste(
             &asset_server,
             &part_registry,
             &mut placed_part,
-            &to_place,
+            part,
         );
-        
-
         if *selected {
             placed_part.insert(Selected{});
         }
+    }
+    println!("imported {:?} parts from {:?}", parts.len(), path);
+}
 
+pub fn compare_with(
+    trigger: Trigger<EditorActionEvent>,
+    mut diff_overlay: ResMut<DiffOverlay>,
+){
+    let EditorActionEvent::CompareWith{path} = trigger.event() else {return;};
+    diff_overlay.dirty = true;
+    let Some(path) = path else {
+        diff_overlay.base = None;
+        return;
+    };
+
+    match ShipFormat::of(Path::new(path)).and_then(|format| format.load(Path::new(path))) {
+        Ok(ship) => {
+            println!("comparing against {:?} parts from {:?}", ship.parts.len(), path);
+            diff_overlay.base = Some(ship.parts);
+        }
+        Err(error) => println!("failed to load {:?} to compare with: {}", path, error),
     }
+}
 
-    
+/// Mirrors the selection across the event's plane, the centreline x = 0 unless `:mirror` named another.
+/// Keeping the originals spawns mirrored copies and moves the selection onto them, parts on the plane
+/// aren't copied and end up deselected with the rest, otherwise the selected parts are moved across in place and stay selected
+pub fn mirror_selected(
+    trigger: Trigger<EditorActionEvent>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    asset_server: Res<AssetServer>,
+    part_registry: Res<PartRegistry>,
+    selected_parts: Query<(Entity, &BasePart, Option<&AdjustableHull>, Option<&Turret>), With<Selected>>,
+    mut commands: Commands,
+){
+    let EditorActionEvent::Mirror{plane, keep_original} = trigger.event() else {return;};
+
+    for (entity, base_part, adjustable_hull, turret) in &selected_parts {
+        let mirrored = mirror_part(&Part::from_optionals((base_part, adjustable_hull, turret)), plane);
+        if !*keep_original {
+            set_part(&mut commands.entity(entity), &mirrored);
+            continue;
+        }
+        //the copies end up selected so the new side can be worked on straight away
+        commands.entity(entity).remove::<Selected>();
+        //parts on the plane would only be doubled up
+        if plane.distance(&base_part.position).abs() < 1e-4 {
+            continue;
+        }
+        let mut placed_part = commands.spawn_empty();
+        place_part(
+            &mut meshes,
+            &mut materials,
+            &asset_server,
+            &part_registry,
+            &mut placed_part,
+            &mirrored,
+        );
+        placed_part.insert(Selected{});
+    }
+}
+
+/// Replaces a part's components with `part`, the meshes update from the change
+pub fn set_part(entity: &mut EntityCommands, part: &Part) {
+    match part {
+        Part::Normal(base_part) => {
+            entity.insert(base_part.clone());
+            entity.remove::<(AdjustableHull, Turret)>();
+        }
+        Part::AdjustableHull(base_part, adjustable_hull) => {
+            entity.insert((base_part.clone(), *adjustable_hull));
+            entity.remove::<Turret>();
+        }
+        Part::Turret(base_part, turret) => {
+            entity.insert((base_part.clone(), *turret));
+            entity.remove::<AdjustableHull>();
+        }
+    }
+}
+
+pub fn undo_redo(
+    trigger: Trigger<EditorActionEvent>,
+    mut editor_data: ResMut<EditorData>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    asset_server: Res<AssetServer>,
+    part_registry: Res<PartRegistry>,
+    mut commands: Commands,
+){
+    let (count, undo) = match trigger.event() {
+        EditorActionEvent::Undo{count} => (*count, true),
+        EditorActionEvent::Redo{count} => (*count, false),
+        _ => {return;}
+    };
+
+    for _ in 0..count {
+        let popped = if undo { editor_data.action_history.pop() } else { editor_data.undone_actions.pop() };
+        let Some(mut action) = popped else {
+            println!("nothing to {}", if undo {"undo"} else {"redo"});
+            break;
+        };
+
+        let mut respawned: Vec<(Entity, Entity)> = Vec::new();
+        for change in &action.changes {
+            let (current, target) = if undo { (&change.after, &change.before) } else { (&change.before, &change.after) };
+            match (current, target) {
+                (_, None) => {
+                    commands.entity(change.entity).despawn_recursive();
+                    if editor_data.latest_selected == Some(change.entity) {
+                        editor_data.latest_selected = None;
+                    }
+                }
+                (Some(_), Some(part)) => {
+                    set_part(&mut commands.entity(change.entity), part);
+                }
+                (None, Some(part)) => {
+                    let mut placed_part = commands.spawn_empty();
+                    place_part(
+                        &mut meshes,
+                        &mut materials,
+                        &asset_server,
+                        &part_registry,
+                        &mut placed_part,
+                        part,
+                    );
+                    respawned.push((change.entity, placed_part.id()));
+                }
+            }
+        }
+
+        //the part came back as a new entity, so everything in the history has to point at it instead
+        let editor_data = editor_data.as_mut();
+        for (from, to) in respawned {
+            action.remap_entity(from, to);
+            if editor_data.latest_selected == Some(from) {
+                editor_data.latest_selected = Some(to);
+            }
+            for other_action in editor_data.action_history.iter_mut().chain(editor_data.undone_actions.iter_mut()) {
+                other_action.remap_entity(from, to);
+            }
+        }
+
+        if undo {
+            editor_data.undone_actions.push(action);
+        } else {
+            editor_data.action_history.push(action);
+        }
+    }
 }
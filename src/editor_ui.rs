@@ -7,7 +7,7 @@ use enum_collections::{EnumMap, Enumerated};
 use rand::{rngs::SmallRng, Rng, SeedableRng};
 use crate::{editor::EditorOptions, transform_gizmo_bevy::GizmoTarget};
 
-use crate::{cam_movement::{spawn_player, EditorCamera}, editor::{CommandData, CommandMode, EditorData, Selected}, editor_actions::EditorActionEvent, editor_utils::{cuboid_face, get_nearby, simple_closest_dist, with_corner_adjacent_adjustable_hulls, AdjHullSide}, parsing::{AdjustableHull, BasePart, Turret}, parts::{base_part_to_bevy_transform, bevy_quat_to_unity, bevy_to_unity_translation, colored_part_material, generate_adjustable_hull_mesh, get_collider, register_all_parts, BasePartMesh, BasePartMeshes, PartAttributes, PartRegistry}};
+use crate::{cam_movement::{spawn_player, EditorCamera}, editor::{CommandData, CommandMode, EditorData, Selected}, editor_actions::EditorActionEvent, editor_utils::{cuboid_face, get_nearby, simple_closest_dist, with_corner_adjacent_adjustable_hulls, AdjHullSide}, parsing::{AdjustableHull, BasePart, Part, ShipHeader, Turret}, ship_diff::diff_parts, ship_stats::{ship_stats_window, update_ship_stats, ShipStats}, hydrostatics::{hydrostatics_window, render_water, spawn_water_plane, update_hydrostatics, Hydrostatics}, stability::{stability_window, update_stability, Stability}, trim::{highlight_contributors, trim_window, update_trim_prediction, TrimPrediction}, parts::{base_part_to_bevy_transform, bevy_quat_to_unity, bevy_to_unity_translation, colored_part_material, generate_adjustable_hull_mesh, get_collider, register_all_parts, BasePartMesh, BasePartMeshes, PartAttributes, PartRegistry}};
 
 pub struct EditorUiPlugin;
 
@@ -78,6 +78,18 @@ impl Plugin for EditorUiPlugin {
         app.add_systems(Startup, setup_ui.after(register_all_parts));
         app.add_systems(Update, egui_update);
         app.insert_resource(TestData { part_thumbnails: HashMap::new() } );
+        app.init_resource::<LoadProblems>();
+        app.init_resource::<DiffOverlay>();
+        app.add_systems(Update, (update_diff_overlay, render_diff_overlay).chain());
+        app.init_resource::<ShipStats>();
+        app.add_systems(Update, (update_ship_stats, ship_stats_window).chain());
+        app.init_resource::<Hydrostatics>();
+        app.add_systems(Startup, spawn_water_plane);
+        app.add_systems(Update, (update_hydrostatics.after(update_ship_stats), hydrostatics_window, render_water).chain());
+        app.init_resource::<Stability>();
+        app.add_systems(Update, (update_stability.after(update_ship_stats), stability_window).chain());
+        app.init_resource::<TrimPrediction>();
+        app.add_systems(Update, (update_trim_prediction.after(update_ship_stats), trim_window, highlight_contributors).chain());
     }
 }
 
@@ -88,6 +100,31 @@ pub enum Language {
     UNSPECIFIED
 }
 
+/// Problems found while loading the ship, shown in their own window until dismissed
+#[derive(Resource, Default)]
+pub struct LoadProblems {
+    pub problems: Vec<String>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DiffStatus {
+    Added,
+    Changed,
+}
+
+/// Outlines parts by how they differ from another version of the ship, green for added, yellow for
+/// changed and red where a removed part used to be. Outlines instead of tinted materials because the
+/// materials carry the part's own colour, which recolouring rewrites and a recolour diff is about,
+/// and removed parts have no entity left to tint
+#[derive(Resource, Default)]
+pub struct DiffOverlay {
+    //the parts being compared against, None when not comparing
+    pub base: Option<Vec<Part>>,
+    pub statuses: HashMap<Entity, DiffStatus>,
+    pub removed: Vec<Part>,
+    pub dirty: bool,
+}
+
 #[derive(Resource)]
 pub struct TestData {
     //pub text: String,
@@ -137,7 +174,10 @@ fn egui_update(
     mut rendered_texture_ids: Local<HashMap<i32,egui::TextureId>>,
     mut is_initialized: Local<bool>,
 
-    mut display_properties: ResMut<PropertiesDisplayData>
+    mut display_properties: ResMut<PropertiesDisplayData>,
+    mut load_problems: ResMut<LoadProblems>,
+    mut ship_header: ResMut<ShipHeader>,
+    mut interchange_path: Local<String>,
 ) {
     contexts.ctx_mut().memory(|mem|{
         match mem.focused() {
@@ -248,12 +288,83 @@ fn egui_update(
         .show(contexts.ctx_mut(), |ui| {
             ui.checkbox(&mut editor_options.floating, "floating");
             ui.checkbox(&mut editor_options.edit_near, "edit_near");
+            ui.checkbox(&mut editor_options.symmetry, "symmetry");
             ui.checkbox(&mut editor_options.group_edit_attributes, "average_attributes");
             ui.checkbox(&mut editor_options.local_gizmo, "local_gizmo");
             ui.checkbox(&mut editor_options.group_gizmos, "group_gizmos");
             ui.checkbox(&mut editor_options.gizmos_activated, "gizmos_activated");
+            if ui.button("Save|保存").clicked() {
+                editor_data.queued_actions.push_front(EditorActionEvent::Save { path: None });
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("File|文件");
+                ui.text_edit_singleline(&mut *interchange_path);
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Export|导出").clicked() {
+                    editor_data.queued_actions.push_front(EditorActionEvent::ExportParts { path: interchange_path.clone() });
+                }
+                if ui.button("Import|导入").clicked() {
+                    editor_data.queued_actions.push_front(EditorActionEvent::ImportParts { path: interchange_path.clone(), selected: true });
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Compare|对比").clicked() {
+                    editor_data.queued_actions.push_front(EditorActionEvent::CompareWith { path: Some(interchange_path.clone()) });
+                }
+                if ui.button("Stop Comparing|停止对比").clicked() {
+                    editor_data.queued_actions.push_front(EditorActionEvent::CompareWith { path: None });
+                }
+            });
+        });
+
+    egui::Window::new("Ship|船")
+        .resizable(Vec2b::new(false,false))
+        .show(contexts.ctx_mut(), |ui| {
+            let ship_header = ship_header.as_mut();
+            egui::Grid::new("ship_metadata").num_columns(2).show(ui, |ui| {
+                ui.label("Name|名称");
+                ui.text_edit_singleline(&mut ship_header.metadata.name);
+                ui.end_row();
+                ui.label("Game Version|游戏版本");
+                ui.text_edit_singleline(&mut ship_header.metadata.game_version);
+                ui.end_row();
+                ui.label("Description|描述");
+                ui.text_edit_multiline(&mut ship_header.metadata.description);
+                ui.end_row();
+
+                //whatever else the save had on its header elements
+                let root_name = ship_header.root_name.clone();
+                for (key, value) in &mut ship_header.root_extras.attributes {
+                    ui.label(format!("{} {}",root_name,key));
+                    ui.text_edit_singleline(value);
+                    ui.end_row();
+                }
+                if let Some(ship_extras) = ship_header.ship_extras.as_mut() {
+                    for (key, value) in &mut ship_extras.attributes {
+                        ui.label(format!("ship {}",key));
+                        ui.text_edit_singleline(value);
+                        ui.end_row();
+                    }
+                }
+            });
         });
 
+    if !load_problems.problems.is_empty() {
+        egui::Window::new("Load Problems|加载问题")
+            .resizable(Vec2b::new(true,true))
+            .scroll(Vec2b::new(false,true))
+            .show(contexts.ctx_mut(), |ui| {
+                for problem in &load_problems.problems {
+                    ui.label(RichText::new(problem).color(Color32::from_rgb(255, 96, 96)));
+                }
+                if ui.button("Dismiss|关闭").clicked() {
+                    load_problems.problems.clear();
+                }
+            });
+    }
+
 
 }
 
@@ -673,11 +784,13 @@ pub fn update_command_text(
             Val::Px(command_display_data.font_width * command_display_data.mult * (command_data.current_byte_index as f32));
 
         let mut history_text = String::new();
-
+        if let Some((register, _)) = &command_data.macros.recording {
+            history_text.push_str(&format!("(recording @{}) ",register));
+        }
 
         let mut count = 0;
         for command in &command_data.command_history {
-            history_text.push_str(command);
+            history_text.push_str(&command.to_string());
             history_text.push_str(" ");
 
             count+=1;
@@ -739,3 +852,60 @@ pub fn on_unhover(
         }
     };
 }
+
+pub fn update_diff_overlay(
+    mut diff_overlay: ResMut<DiffOverlay>,
+    changed_parts: Query<(), Or<(Changed<BasePart>, Changed<AdjustableHull>, Changed<Turret>)>>,
+    mut removed_parts: RemovedComponents<BasePart>,
+    all_parts: Query<(Entity, &BasePart, Option<&AdjustableHull>, Option<&Turret>)>,
+){
+    let parts_changed = !changed_parts.is_empty() || removed_parts.read().count() > 0;
+    if !diff_overlay.dirty && !parts_changed { return; }
+    let diff_overlay = diff_overlay.as_mut();
+    diff_overlay.dirty = false;
+    diff_overlay.statuses.clear();
+    diff_overlay.removed.clear();
+    let Some(base) = diff_overlay.base.as_deref() else { return; };
+
+    let mut entities: Vec<Entity> = Vec::new();
+    let mut current: Vec<Part> = Vec::new();
+    for part in &all_parts {
+        entities.push(part.0);
+        current.push(Part::from_optionals((part.1, part.2, part.3)));
+    }
+
+    let diff = diff_parts(base, &current);
+    for index in &diff.added {
+        diff_overlay.statuses.insert(entities[*index], DiffStatus::Added);
+    }
+    for part_match in diff.changed() {
+        diff_overlay.statuses.insert(entities[part_match.new_index], DiffStatus::Changed);
+    }
+    for index in &diff.removed {
+        diff_overlay.removed.push(base[*index].clone());
+    }
+}
+
+pub fn render_diff_overlay(
+    diff_overlay: Res<DiffOverlay>,
+    all_parts: Query<(&BasePart, Option<&AdjustableHull>)>,
+    part_registry: Res<PartRegistry>,
+    mut gizmo: Gizmos
+){
+    if diff_overlay.base.is_none() { return; }
+
+    for (entity, status) in &diff_overlay.statuses {
+        let Ok(part) = all_parts.get(*entity) else {continue;};
+        let Some(part_data) = part_registry.parts.get(&part.0.id) else {continue;};
+        let color = match status {
+            DiffStatus::Added => Color::srgb_u8(0, 255, 96),
+            DiffStatus::Changed => Color::srgb_u8(255, 200, 0),
+        };
+        gizmo.cuboid(get_collider(part.0, part.1, part_data), color);
+    }
+    for removed in &diff_overlay.removed {
+        let (base_part, adjustable_hull, _) = removed.to_optionals();
+        let Some(part_data) = part_registry.parts.get(&base_part.id) else {continue;};
+        gizmo.cuboid(get_collider(base_part, adjustable_hull, part_data), Color::srgb_u8(255, 32, 32));
+    }
+}
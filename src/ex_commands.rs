@@ -0,0 +1,162 @@
+//! `:` commands typed into the command bar, like vim's ex commands.
+//!
+//! `:w [path]` saves, `:e path` opens a ship, `:set floating` / `:set nofloating` / `:set floating!`
+//! change editor settings, `:select id=5` selects every part with that attribute value,
+//! `:color #ff0000` recolors the selection and `:lang en` switches the language.
+//! `:mirror [x|y|z] [offset]` copies the selection across a plane, the centreline if left out,
+//! `:mirror!` moves it across instead.
+
+use enum_collections::Enumerated;
+
+use crate::{editor::{EditorData, EditorOptions}, editor_actions::{EditorActionEvent, EditorSettingChange}, editor_ui::Language, mirror::MirrorPlane, parts::PartAttributes};
+
+pub struct ExCommand {
+    pub name: &'static str,
+    pub usage: &'static str,
+    //what the first argument can be completed to
+    pub arguments: fn() -> Vec<String>,
+}
+
+pub const EX_COMMANDS: &[ExCommand] = &[
+    ExCommand { name: "w", usage: ":w [path]", arguments: no_arguments },
+    ExCommand { name: "e", usage: ":e path", arguments: no_arguments },
+    ExCommand { name: "set", usage: ":set [no]setting[!]", arguments: setting_names },
+    ExCommand { name: "select", usage: ":select attribute=value", arguments: attribute_arguments },
+    ExCommand { name: "color", usage: ":color #rrggbb", arguments: no_arguments },
+    ExCommand { name: "lang", usage: ":lang en|cn", arguments: language_names },
+    ExCommand { name: "mirror", usage: ":mirror[!] [x|y|z] [offset]", arguments: axis_names },
+];
+
+const SETTINGS: &[&str] = &["floating", "edit_near", "symmetry"];
+
+fn no_arguments() -> Vec<String> {
+    return Vec::new();
+}
+
+fn setting_names() -> Vec<String> {
+    return SETTINGS.iter().map(|setting| setting.to_string()).collect();
+}
+
+fn attribute_arguments() -> Vec<String> {
+    return PartAttributes::VARIANTS.iter().map(|attribute| format!("{}=",attribute_name(attribute))).collect();
+}
+
+fn language_names() -> Vec<String> {
+    return vec!["en".to_owned(), "cn".to_owned()];
+}
+
+fn axis_names() -> Vec<String> {
+    return vec!["x".to_owned(), "y".to_owned(), "z".to_owned()];
+}
+
+/// PositionX -> position_x
+fn attribute_name(attribute: &PartAttributes) -> String {
+    let mut name = String::new();
+    for (index, char) in format!("{:?}",attribute).chars().enumerate() {
+        if char.is_uppercase() && index != 0 {
+            name.push('_');
+        }
+        name.push(char.to_ascii_lowercase());
+    }
+    return name;
+}
+
+fn attribute_named(name: &str) -> Option<PartAttributes> {
+    let name = name.to_lowercase().replace('_', "");
+    return PartAttributes::VARIANTS.iter().find(|attribute| attribute_name(attribute).replace('_', "") == name).copied();
+}
+
+/// Completes the last word of a line as far as every candidate agrees, like a shell
+pub fn complete(line: &str) -> String {
+    let (head, word, candidates) = match line.rsplit_once(' ') {
+        None => (String::new(), line, EX_COMMANDS.iter().map(|command| command.name.to_owned()).collect::<Vec<String>>()),
+        Some((head, word)) => {
+            let Some(command) = EX_COMMANDS.iter().find(|command| command.name == head.trim()) else {
+                return line.to_owned();
+            };
+            (format!("{} ",head), word, (command.arguments)())
+        },
+    };
+
+    let matching: Vec<&String> = candidates.iter().filter(|candidate| candidate.starts_with(word)).collect();
+    let Some(first) = matching.first() else { return line.to_owned(); };
+
+    let mut common = first.len();
+    for candidate in &matching {
+        common = common.min(first.bytes().zip(candidate.bytes()).take_while(|pair| pair.0 == pair.1).count());
+    }
+
+    let mut completed = format!("{}{}",head,&first[..common]);
+    //a finished command name gets its space so the arguments can be typed straight away
+    if matching.len() == 1 && head.is_empty() {
+        completed.push(' ');
+    }
+    return completed;
+}
+
+/// Queues the actions for an ex command line, without the `:`
+pub fn run_ex(line: &str, editor_data: &mut EditorData, editor_options: &EditorOptions) -> Result<(), String> {
+    let line = line.trim();
+    let (name, argument) = line.split_once(' ').map(|(name, argument)| (name, argument.trim())).unwrap_or((line, ""));
+
+    let action = match name {
+        "w" => EditorActionEvent::Save { path: if argument.is_empty() {None} else {Some(argument.to_owned())} },
+        "e" => {
+            if argument.is_empty() {
+                return Err(format!("usage: :e path"));
+            }
+            EditorActionEvent::Open { path: argument.to_owned() }
+        },
+        "set" => {
+            let (setting, value) = if let Some(setting) = argument.strip_suffix('!') {
+                (setting, None)
+            } else if let Some(setting) = argument.strip_prefix("no").filter(|setting| SETTINGS.contains(setting)) {
+                (setting, Some(false))
+            } else {
+                (argument, Some(true))
+            };
+            match setting {
+                "floating" => EditorActionEvent::SetEditorSetting { change: EditorSettingChange {
+                    floating: Some(value.unwrap_or(!editor_options.floating)), ..Default::default()
+                }},
+                "edit_near" => EditorActionEvent::SetEditorSetting { change: EditorSettingChange {
+                    edit_near: Some(value.unwrap_or(!editor_options.edit_near)), ..Default::default()
+                }},
+                "symmetry" => EditorActionEvent::SetEditorSetting { change: EditorSettingChange {
+                    symmetry: Some(value.unwrap_or(!editor_options.symmetry)), ..Default::default()
+                }},
+                _ => return Err(format!("unknown setting {:?}",setting)),
+            }
+        },
+        "select" => {
+            let Some((attribute, value)) = argument.split_once('=') else {
+                return Err(format!("usage: :select attribute=value"));
+            };
+            let Some(attribute) = attribute_named(attribute.trim()) else {
+                return Err(format!("unknown attribute {:?}",attribute));
+            };
+            EditorActionEvent::Select { attribute, value: value.trim().to_owned() }
+        },
+        "color" => {
+            if argument.is_empty() {
+                return Err(format!("usage: :color #rrggbb"));
+            }
+            EditorActionEvent::SetAttribute { attribute: Some(PartAttributes::Color), value: argument.to_owned() }
+        },
+        "lang" => {
+            let language = match argument.to_lowercase().as_str() {
+                "en" => Language::EN,
+                "cn" | "zh" => Language::CN,
+                _ => return Err(format!("unknown language {:?}",argument)),
+            };
+            EditorActionEvent::SetEditorSetting { change: EditorSettingChange { language: Some(language), ..Default::default() } }
+        },
+        "mirror" | "mirror!" => EditorActionEvent::Mirror { plane: MirrorPlane::parse(argument)?, keep_original: name == "mirror" },
+        _ => return Err(format!(
+            "unknown command {:?}, the commands are {}",
+            name,EX_COMMANDS.iter().map(|command| command.usage).collect::<Vec<&str>>().join(" ")
+        )),
+    };
+    editor_data.queued_actions.push_front(action);
+    return Ok(());
+}
@@ -0,0 +1,170 @@
+//! Volume, centroid and wetted area of adjustable hulls, from the outline formula in
+//! `adjustable_hull_section_point` instead of the mesh.
+//!
+//! A hull is its back outline blended into its front outline along its length, each point of the
+//! outline moving in a straight line. The outlines are sampled much finer than the mesh and the
+//! blend is integrated exactly: a cross section's area is quadratic in how far along it is and
+//! its moments are cubic, which three point Gauss-Legendre covers.
+
+use core::f32;
+
+use bevy::math::{Vec2, Vec3};
+
+use crate::{parsing::AdjustableHull, parts::adjustable_hull_section_point};
+
+/// Points around each outline, a multiple of 8 so the corners of square hulls are sampled
+pub const SECTION_RESOLUTION: usize = 720;
+
+//nodes and weights on 0..1
+const GAUSS_LEGENDRE: [(f32, f32); 3] = [
+    (0.112701665, 5.0/18.0),
+    (0.5, 8.0/18.0),
+    (0.887298335, 5.0/18.0),
+];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HullGeometry {
+    pub volume: f32,
+    //in the part's local space, like the mesh
+    pub centroid: Vec3,
+    //just the sides, the ends are usually against the next hull along
+    pub wetted_area: f32,
+}
+
+fn outline(adjustable_hull: &AdjustableHull, resolution: usize, front: bool) -> Vec<Vec2> {
+    return (0..resolution).map(|i| {
+        let angle = f32::consts::TAU*((i as f32)/(resolution as f32));
+        return Vec2::from(adjustable_hull_section_point(adjustable_hull, angle, front));
+    }).collect();
+}
+
+pub fn adjustable_hull_geometry(adjustable_hull: &AdjustableHull) -> HullGeometry {
+    return adjustable_hull_geometry_at(adjustable_hull, SECTION_RESOLUTION);
+}
+
+/// With the outlines sampled at `resolution` points, at the mesh's resolution this is the mesh
+/// with its side quads taken as curved instead of split into triangles
+pub fn adjustable_hull_geometry_at(adjustable_hull: &AdjustableHull, resolution: usize) -> HullGeometry {
+    let front = outline(adjustable_hull, resolution, true);
+    let back = outline(adjustable_hull, resolution, false);
+    let length = adjustable_hull.length;
+
+    let mut volume: f32 = 0.0;
+    let mut moment = Vec3::ZERO;
+    for (along, weight) in GAUSS_LEGENDRE {
+        let z = (along-0.5)*length;
+        let mut area: f32 = 0.0;
+        let mut area_moment = Vec2::ZERO;
+        for i in 0..resolution {
+            let a = back[i].lerp(front[i], along);
+            let b = back[(i+1)%resolution].lerp(front[(i+1)%resolution], along);
+            let cross = a.perp_dot(b);
+            area += cross/2.0;
+            area_moment += (a+b)*cross/6.0;
+        }
+        volume += weight*area*length;
+        moment += weight*length*area_moment.extend(area*z);
+    }
+
+    let mut wetted_area: f32 = 0.0;
+    for i in 0..resolution {
+        let j = (i+1)%resolution;
+        for (along, along_weight) in GAUSS_LEGENDRE {
+            let around = (back[j].lerp(front[j], along) - back[i].lerp(front[i], along)).extend(0.0);
+            for (across, across_weight) in GAUSS_LEGENDRE {
+                let lengthwise = (front[i]-back[i]).lerp(front[j]-back[j], across).extend(length);
+                wetted_area += along_weight*across_weight*around.cross(lengthwise).length();
+            }
+        }
+    }
+
+    if volume == 0.0 {
+        return HullGeometry { volume: 0.0, centroid: Vec3::ZERO, wetted_area };
+    }
+    return HullGeometry { volume: volume.abs(), centroid: moment/volume, wetted_area };
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec3;
+
+    use crate::{parsing::AdjustableHull, parts::adjustable_hull_triangles};
+
+    use super::{adjustable_hull_geometry, adjustable_hull_geometry_at};
+
+    //signed volume and centroid of a closed triangle mesh, from the tetrahedrons to the origin
+    fn mesh_volume(vertices: &[[f32; 3]], indices: &[u32]) -> (f32, Vec3) {
+        let mut volume: f32 = 0.0;
+        let mut moment = Vec3::ZERO;
+        for triangle in indices.chunks_exact(3) {
+            let a = Vec3::from(vertices[triangle[0] as usize]);
+            let b = Vec3::from(vertices[triangle[1] as usize]);
+            let c = Vec3::from(vertices[triangle[2] as usize]);
+            let tetrahedron = a.dot(b.cross(c))/6.0;
+            volume += tetrahedron;
+            moment += tetrahedron*(a+b+c)/4.0;
+        }
+        return (volume, moment/volume);
+    }
+
+    fn tapered() -> AdjustableHull {
+        return AdjustableHull {
+            length: 4.0,
+            height: 4.7,
+            front_width: 0.25,
+            back_width: 2.65,
+            front_spread: 0.765,
+            back_spread: 1.02,
+            top_roundness: 0.0,
+            bottom_roundness: 1.0,
+            height_scale: 0.94175,
+            height_offset: 0.01912,
+        };
+    }
+
+    fn relative_error(a: f32, b: f32) -> f32 {
+        return (a-b).abs()/b.abs();
+    }
+
+    #[test]
+    fn box_hull_is_exact() {
+        let adjustable_hull = AdjustableHull {
+            length: 3.0, height: 2.0, front_width: 5.0, back_width: 5.0, bottom_roundness: 0.0, ..Default::default()
+        };
+        let geometry = adjustable_hull_geometry(&adjustable_hull);
+        assert!(relative_error(geometry.volume, 30.0) < 1e-4, "{:?}", geometry);
+        assert!(geometry.centroid.length() < 1e-4, "{:?}", geometry);
+        assert!(relative_error(geometry.wetted_area, 2.0*(5.0+2.0)*3.0) < 1e-4, "{:?}", geometry);
+    }
+
+    #[test]
+    fn round_hull_is_an_elliptic_cylinder() {
+        let adjustable_hull = AdjustableHull {
+            length: 2.0, height: 3.0, front_width: 4.0, back_width: 4.0, top_roundness: 1.0, bottom_roundness: 1.0, ..Default::default()
+        };
+        let geometry = adjustable_hull_geometry(&adjustable_hull);
+        assert!(relative_error(geometry.volume, std::f32::consts::PI*2.0*1.5*2.0) < 1e-4, "{:?}", geometry);
+        assert!(geometry.centroid.length() < 1e-4, "{:?}", geometry);
+    }
+
+    #[test]
+    fn matches_the_mesh_at_its_resolution() {
+        for adjustable_hull in [AdjustableHull::default(), tapered()] {
+            let (vertices, indices) = adjustable_hull_triangles(&adjustable_hull);
+            let (mesh_volume, mesh_centroid) = mesh_volume(&vertices, &indices);
+            let geometry = adjustable_hull_geometry_at(&adjustable_hull, 6*4);
+            assert!(relative_error(geometry.volume, mesh_volume.abs()) < 1e-3, "{:?} against {}", geometry, mesh_volume);
+            //the mesh splits every side quad along the same diagonal, which leans its centroid sideways a little
+            assert!(geometry.centroid.distance(mesh_centroid) < 5e-3*adjustable_hull.length, "{:?} against {}", geometry, mesh_centroid);
+        }
+    }
+
+    #[test]
+    fn finer_than_the_mesh_stays_close_to_it() {
+        let adjustable_hull = tapered();
+        let (vertices, indices) = adjustable_hull_triangles(&adjustable_hull);
+        let (mesh_volume, _) = mesh_volume(&vertices, &indices);
+        let geometry = adjustable_hull_geometry(&adjustable_hull);
+        assert!(relative_error(geometry.volume, mesh_volume.abs()) < 0.02, "{:?} against {}", geometry, mesh_volume);
+    }
+}
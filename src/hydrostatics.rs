@@ -0,0 +1,409 @@
+//! Where the ship floats and how stable it is upright, the "Hydrostatics" window.
+//!
+//! Every part is a closed triangle mesh, adjustable hulls from their mesh and everything else as
+//! its collider box, and the water is a plane. The part of each mesh under the plane is clipped
+//! off and its volume summed from tetrahedrons with their tip on the plane, so the cut face adds
+//! nothing to the volume and only has to be traced for the waterplane. Parts that overlap count
+//! twice, which mostly matters for parts fitted inside hulls below the waterline.
+//!
+//! The waterline is either set by hand or found from the ship's mass by bisection.
+//! Everything is worked out in bevy space and shown in unity space.
+
+use bevy::{color::{Alpha, Color}, math::{Vec2, Vec3}, pbr::{MeshMaterial3d, StandardMaterial}, picking::PickingBehavior, prelude::{AlphaMode, Assets, Changed, Commands, Component, Gizmos, Mesh, Mesh3d, Meshable, Or, Plane3d, Query, RemovedComponents, Res, ResMut, Resource, Transform, Visibility, With}};
+use bevy_egui::{egui::{self, Vec2b}, EguiContexts};
+
+use crate::{parsing::{AdjustableHull, BasePart, Turret}, parts::{adjustable_hull_triangles, base_part_to_bevy_transform, bevy_to_unity_translation, get_collider, unity_to_bevy_translation, PartRegistry}, ship_stats::ShipStats};
+
+/// Sea water, in the same units as part densities
+pub const DEFAULT_WATER_DENSITY: f32 = 1.025;
+const BISECTION_STEPS: usize = 40;
+
+pub type Triangle = [Vec3; 3];
+
+/// Each part as a closed mesh, hulls from their mesh and the rest from their collider
+pub fn part_meshes<'a>(parts: impl Iterator<Item = (&'a BasePart, Option<&'a AdjustableHull>)>, part_registry: &PartRegistry) -> Vec<Vec<Triangle>> {
+    let mut meshes = Vec::new();
+    for (base_part, adjustable_hull) in parts {
+        if let Some(adjustable_hull) = adjustable_hull {
+            let transform = base_part_to_bevy_transform(base_part);
+            let (vertices, indices) = adjustable_hull_triangles(adjustable_hull);
+            meshes.push(indices.chunks_exact(3).map(|triangle| {
+                return [0, 1, 2].map(|corner| transform.transform_point(Vec3::from(vertices[triangle[corner] as usize])));
+            }).collect());
+            continue;
+        }
+        let Some(part_data) = part_registry.parts.get(&base_part.id) else {continue;};
+        meshes.push(box_triangles(&get_collider(base_part, None, part_data)));
+    }
+    return meshes;
+}
+
+fn box_triangles(collider: &Transform) -> Vec<Triangle> {
+    let corner = |x: f32, y: f32, z: f32| collider.transform_point(Vec3::new(x, y, z));
+    let mut triangles = Vec::with_capacity(12);
+    for axis in 0..3 {
+        for side in [-0.5, 0.5] {
+            //the four corners of the face going around it
+            let quad = [(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)].map(|(a, b)| {
+                let mut local = [0.0; 3];
+                local[axis] = side;
+                local[(axis+1)%3] = a;
+                local[(axis+2)%3] = b;
+                return corner(local[0], local[1], local[2]);
+            });
+            let outward = quad[0]+quad[2] - 2.0*collider.translation;
+            for mut triangle in [[quad[0], quad[1], quad[2]], [quad[0], quad[2], quad[3]]] {
+                if (triangle[1]-triangle[0]).cross(triangle[2]-triangle[0]).dot(outward) < 0.0 {
+                    triangle.swap(1, 2);
+                }
+                triangles.push(triangle);
+            }
+        }
+    }
+    return triangles;
+}
+
+/// What's under a water plane
+#[derive(Debug, Clone, Default)]
+pub struct Submerged {
+    pub volume: f32,
+    pub centroid: Vec3,
+    pub waterplane_area: f32,
+    //about the axis through the waterplane's centroid along the ship, for the metacentric radius
+    pub transverse_inertia: f32,
+    //where the water meets the parts
+    pub segments: Vec<[Vec3; 2]>,
+    //how far the waterplane reaches across and along the ship
+    pub waterline_beam: f32,
+    pub waterline_length: f32,
+}
+
+/// Clips every mesh below the plane through `origin` with `normal` pointing out of the water
+pub fn submerged(meshes: &[Vec<Triangle>], origin: Vec3, normal: Vec3) -> Submerged {
+    let normal = normal.normalize();
+    //the waterplane's own axes, across and along the ship
+    let across = normal.cross(Vec3::Z).try_normalize().unwrap_or(Vec3::X);
+    let along = across.cross(normal);
+
+    let mut result = Submerged::default();
+    let mut moment = Vec3::ZERO;
+    let mut first_across: f32 = 0.0;
+    let mut second_across: f32 = 0.0;
+    let mut across_range = (f32::INFINITY, f32::NEG_INFINITY);
+    let mut along_range = (f32::INFINITY, f32::NEG_INFINITY);
+
+    for mesh in meshes {
+        let mut volume: f32 = 0.0;
+        let mut mesh_moment = Vec3::ZERO;
+        let mut area: f32 = 0.0;
+        let mut mesh_first: f32 = 0.0;
+        let mut mesh_second: f32 = 0.0;
+        let mut segments: Vec<[Vec3; 2]> = Vec::new();
+
+        for triangle in mesh {
+            let (polygon, segment) = clip_below(triangle, origin, normal);
+            for i in 1..polygon.len().saturating_sub(1) {
+                let (a, b, c) = (polygon[0]-origin, polygon[i]-origin, polygon[i+1]-origin);
+                let tetrahedron = a.dot(b.cross(c))/6.0;
+                volume += tetrahedron;
+                mesh_moment += tetrahedron*(a+b+c)/4.0;
+            }
+            let Some([start, end]) = segment else {continue;};
+            let a = Vec2::new((start-origin).dot(across), (start-origin).dot(along));
+            let b = Vec2::new((end-origin).dot(across), (end-origin).dot(along));
+            let cross = a.perp_dot(b);
+            area += cross/2.0;
+            mesh_first += cross*(a.x+b.x)/6.0;
+            mesh_second += cross*(a.x*a.x + a.x*b.x + b.x*b.x)/12.0;
+            segments.push([start, end]);
+        }
+
+        //however the mesh is wound, what's under water is never negative
+        if volume < 0.0 {
+            volume = -volume;
+            mesh_moment = -mesh_moment;
+        }
+        if area < 0.0 {
+            area = -area;
+            mesh_first = -mesh_first;
+            mesh_second = -mesh_second;
+        }
+        result.volume += volume;
+        moment += mesh_moment;
+        result.waterplane_area += area;
+        first_across += mesh_first;
+        second_across += mesh_second;
+        for segment in &segments {
+            for point in segment {
+                let (point_across, point_along) = ((*point-origin).dot(across), (*point-origin).dot(along));
+                across_range = (across_range.0.min(point_across), across_range.1.max(point_across));
+                along_range = (along_range.0.min(point_along), along_range.1.max(point_along));
+            }
+        }
+        result.segments.extend(segments);
+    }
+
+    if result.volume > 0.0 {
+        result.centroid = origin + moment/result.volume;
+    }
+    if result.waterplane_area > 0.0 {
+        let centre_across = first_across/result.waterplane_area;
+        result.transverse_inertia = second_across - result.waterplane_area*centre_across*centre_across;
+        result.waterline_beam = across_range.1-across_range.0;
+        result.waterline_length = along_range.1-along_range.0;
+    }
+    return result;
+}
+
+/// The part of a triangle under the plane, and the edge it was cut along going the triangle's way around
+fn clip_below(triangle: &Triangle, origin: Vec3, normal: Vec3) -> (Vec<Vec3>, Option<[Vec3; 2]>) {
+    let heights = triangle.map(|point| (point-origin).dot(normal));
+    let mut polygon: Vec<Vec3> = Vec::with_capacity(4);
+    let mut cut_start = None;
+    let mut cut_end = None;
+    for i in 0..3 {
+        let j = (i+1)%3;
+        if heights[i] <= 0.0 {
+            polygon.push(triangle[i]);
+        }
+        if (heights[i] <= 0.0) != (heights[j] <= 0.0) {
+            let crossing = triangle[i].lerp(triangle[j], heights[i]/(heights[i]-heights[j]));
+            polygon.push(crossing);
+            //leaving the water starts the cut, coming back ends it
+            if heights[i] <= 0.0 { cut_start = Some(crossing); } else { cut_end = Some(crossing); }
+        }
+    }
+    let segment = match (cut_start, cut_end) {
+        (Some(start), Some(end)) => Some([start, end]),
+        _ => None,
+    };
+    return (polygon, segment);
+}
+
+/// Highest and lowest point of all the meshes along `normal`
+pub fn extent(meshes: &[Vec<Triangle>], normal: Vec3) -> Option<(f32, f32)> {
+    let mut range = (f32::INFINITY, f32::NEG_INFINITY);
+    for point in meshes.iter().flatten().flatten() {
+        let height = point.dot(normal);
+        range = (range.0.min(height), range.1.max(height));
+    }
+    if range.0 > range.1 { return None; }
+    return Some(range);
+}
+
+/// How far along `normal` the water has to be to float `volume`, None if everything under water isn't enough
+pub fn waterline_for_volume(meshes: &[Vec<Triangle>], normal: Vec3, volume: f32) -> Option<f32> {
+    let (mut low, mut high) = extent(meshes, normal)?;
+    if submerged(meshes, normal*high, normal).volume < volume {
+        return None;
+    }
+    for _ in 0..BISECTION_STEPS {
+        let middle = (low+high)/2.0;
+        if submerged(meshes, normal*middle, normal).volume < volume {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    return Some((low+high)/2.0);
+}
+
+#[derive(Debug, Clone)]
+pub struct HydrostaticResult {
+    pub waterline: f32,
+    pub draft: f32,
+    pub displacement: f32,
+    pub submerged: Submerged,
+    pub metacentric_radius: f32,
+    //None without a center of mass
+    pub metacentric_height: Option<f32>,
+    pub block_coefficient: f32,
+}
+
+#[derive(Resource)]
+pub struct Hydrostatics {
+    pub enabled: bool,
+    //None solves it from the ship's mass
+    pub fixed_waterline: Option<f32>,
+    pub water_density: f32,
+    pub result: Result<HydrostaticResult, String>,
+    pub dirty: bool,
+}
+
+impl Default for Hydrostatics {
+    fn default() -> Self {
+        return Hydrostatics {
+            enabled: false,
+            fixed_waterline: None,
+            water_density: DEFAULT_WATER_DENSITY,
+            result: Err("not worked out yet".to_owned()),
+            dirty: true,
+        };
+    }
+}
+
+pub fn hydrostatics_at(meshes: &[Vec<Triangle>], waterline: f32, water_density: f32, center_of_mass: Option<Vec3>) -> Result<HydrostaticResult, String> {
+    let Some((keel, _)) = extent(meshes, Vec3::Y) else { return Err("there are no parts".to_owned()); };
+    let submerged = submerged(meshes, Vec3::Y*waterline, Vec3::Y);
+    if submerged.volume <= 0.0 {
+        return Err("nothing is under the waterline".to_owned());
+    }
+
+    let draft = waterline-keel;
+    let metacentric_radius = submerged.transverse_inertia/submerged.volume;
+    let box_volume = submerged.waterline_length*submerged.waterline_beam*draft;
+    return Ok(HydrostaticResult {
+        waterline,
+        draft,
+        displacement: submerged.volume*water_density,
+        metacentric_radius,
+        //GM = KB + BM - KG, the keel cancels out
+        metacentric_height: center_of_mass.map(|center| submerged.centroid.y + metacentric_radius - center.y),
+        block_coefficient: if box_volume > 0.0 { submerged.volume/box_volume } else { 0.0 },
+        submerged,
+    });
+}
+
+pub fn update_hydrostatics(
+    mut hydrostatics: ResMut<Hydrostatics>,
+    ship_stats: Res<ShipStats>,
+    changed_parts: Query<(), Or<(Changed<BasePart>, Changed<AdjustableHull>, Changed<Turret>)>>,
+    mut removed_parts: RemovedComponents<BasePart>,
+    parts: Query<(&BasePart, Option<&AdjustableHull>)>,
+    part_registry: Res<PartRegistry>,
+){
+    if !changed_parts.is_empty() || removed_parts.read().count() > 0 {
+        hydrostatics.dirty = true;
+    }
+    if !hydrostatics.enabled || !hydrostatics.dirty { return; }
+    hydrostatics.dirty = false;
+
+    let meshes = part_meshes(parts.iter(), &part_registry);
+    //stats are in unity space
+    let center_of_mass = ship_stats.center_of_mass().map(|center| unity_to_bevy_translation(&center));
+    let waterline = match hydrostatics.fixed_waterline {
+        Some(waterline) => Some(waterline),
+        None => waterline_for_volume(&meshes, Vec3::Y, ship_stats.mass/hydrostatics.water_density),
+    };
+    hydrostatics.result = match waterline {
+        Some(waterline) => hydrostatics_at(&meshes, waterline, hydrostatics.water_density, center_of_mass),
+        None => Err("the ship sinks, it weighs more than the water all of it can push away".to_owned()),
+    };
+}
+
+pub fn hydrostatics_window(
+    mut contexts: EguiContexts,
+    mut hydrostatics: ResMut<Hydrostatics>,
+){
+    let hydrostatics = hydrostatics.as_mut();
+
+    egui::Window::new("Hydrostatics|静水力")
+        .resizable(Vec2b::new(false,false))
+        .show(contexts.ctx_mut(), |ui| {
+            let mut changed = ui.checkbox(&mut hydrostatics.enabled, "Show Water|显示水面").changed();
+
+            let mut fixed = hydrostatics.fixed_waterline.is_some();
+            changed |= ui.checkbox(&mut fixed, "Fixed Waterline|固定水线").changed();
+            if fixed != hydrostatics.fixed_waterline.is_some() {
+                let current = hydrostatics.result.as_ref().map(|result| result.waterline).unwrap_or(0.0);
+                hydrostatics.fixed_waterline = if fixed { Some(current) } else { None };
+            }
+            egui::Grid::new("hydrostatics_settings").num_columns(2).show(ui, |ui| {
+                if let Some(waterline) = hydrostatics.fixed_waterline.as_mut() {
+                    ui.label("Waterline|水线");
+                    changed |= ui.add(egui::DragValue::new(waterline).speed(0.05)).changed();
+                    ui.end_row();
+                }
+                ui.label("Water Density|水密度");
+                changed |= ui.add(egui::DragValue::new(&mut hydrostatics.water_density).speed(0.005).range(0.01..=f32::MAX)).changed();
+                ui.end_row();
+            });
+            hydrostatics.dirty |= changed;
+            if !hydrostatics.enabled { return; }
+
+            ui.separator();
+            let result = match &hydrostatics.result {
+                Ok(result) => result,
+                Err(problem) => {
+                    ui.label(problem);
+                    return;
+                },
+            };
+            egui::Grid::new("hydrostatics").num_columns(2).show(ui, |ui| {
+                let centroid = bevy_to_unity_translation(&result.submerged.centroid);
+                let rows = [
+                    ("Waterline|水线", format!("{:.3}",result.waterline)),
+                    ("Draft|吃水", format!("{:.3}",result.draft)),
+                    ("Displacement|排水量", format!("{:.1}",result.displacement)),
+                    ("Displaced Volume|排水体积", format!("{:.2}",result.submerged.volume)),
+                    ("Waterplane Area|水线面积", format!("{:.2}",result.submerged.waterplane_area)),
+                    ("Waterline Length|水线长", format!("{:.2}",result.submerged.waterline_length)),
+                    ("Waterline Beam|水线宽", format!("{:.2}",result.submerged.waterline_beam)),
+                    ("Center of Buoyancy|浮心", format!("{:.2} {:.2} {:.2}",centroid.x,centroid.y,centroid.z)),
+                    ("BM|稳心半径", format!("{:.3}",result.metacentric_radius)),
+                    ("GM|稳心高", result.metacentric_height.map_or("-".to_owned(), |height| format!("{:.3}",height))),
+                    ("Block Coefficient|方形系数", format!("{:.3}",result.block_coefficient)),
+                ];
+                for (label, value) in rows {
+                    ui.label(label);
+                    ui.label(value);
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+#[derive(Component)]
+pub struct WaterPlane {}
+
+pub fn spawn_water_plane(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut commands: Commands,
+){
+    let mut material = StandardMaterial::from_color(Color::srgb(0.1, 0.35, 0.8).with_alpha(0.35));
+    material.alpha_mode = AlphaMode::Blend;
+    material.double_sided = true;
+    material.cull_mode = None;
+    commands.spawn((
+        WaterPlane{},
+        Mesh3d(meshes.add(Plane3d::default().mesh().size(1.0, 1.0))),
+        MeshMaterial3d(materials.add(material)),
+        Transform::default(),
+        Visibility::Hidden,
+        //parts under the water still have to be clickable
+        PickingBehavior::IGNORE,
+    ));
+}
+
+pub fn render_water(
+    hydrostatics: Res<Hydrostatics>,
+    mut water_plane: Query<(&mut Transform, &mut Visibility), With<WaterPlane>>,
+    mut gizmo: Gizmos,
+){
+    let Ok((mut transform, mut visibility)) = water_plane.get_single_mut() else {return;};
+    let result = hydrostatics.result.as_ref().ok().filter(|_| hydrostatics.enabled);
+    let Some(result) = result else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    *visibility = Visibility::Visible;
+    let (mut min, mut max) = (Vec3::INFINITY, Vec3::NEG_INFINITY);
+    for segment in &result.submerged.segments {
+        gizmo.line(segment[0], segment[1], Color::srgb_u8(0, 200, 255));
+        for point in segment {
+            min = min.min(*point);
+            max = max.max(*point);
+        }
+    }
+    if min.x > max.x {
+        min = Vec3::ZERO;
+        max = Vec3::ZERO;
+    }
+    //a bit of water around the waterline
+    let center = (min+max)/2.0;
+    let size = (max-min).max(Vec3::ONE)*1.5;
+    transform.translation = Vec3::new(center.x, result.waterline, center.z);
+    transform.scale = Vec3::new(size.x, 1.0, size.z);
+}
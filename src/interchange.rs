@@ -0,0 +1,180 @@
+//! JSON and RON versions of ships and parts, for generating parts from scripts and hand editing.
+//!
+//! A part is one flat object, using the unity coordinates and degrees the game uses:
+//!
+//! ```json
+//! {
+//!   "id": 0,
+//!   "ignore_physics": false,
+//!   "position": [1.5, -2.0, 10.25],
+//!   "rotation": [0.0, 90.0, 0.0],
+//!   "scale": [1.0, 1.0, 1.0],
+//!   "color": "#A0B0C0",
+//!   "armor": 25,
+//!   "adjustable_hull": {
+//!     "length": 6.0, "height": 4.5,
+//!     "front_width": 2.0, "back_width": 3.0,
+//!     "front_spread": 0.5, "back_spread": 0.0,
+//!     "top_roundness": 0.0, "bottom_roundness": 1.0,
+//!     "height_scale": 1.0, "height_offset": 0.0
+//!   }
+//! }
+//! ```
+//!
+//! `adjustable_hull` is only there for adjustable hulls, and turrets get
+//! `"turret": {"manual_control": true, "elevator": 12.5}` (`elevator` is optional) instead.
+//! Every field can be left out to use its default. `extras` holds xml the editor doesn't model
+//! and only shows up when a part had some.
+//!
+//! A parts file is a list of parts. A ship file is `{"header": {...}, "parts": [...]}` where the
+//! header has `metadata` (`name`, `game_version`, `description`) and the rest of the save's header xml.
+//! RON files use the same field names.
+
+use std::{error::Error, fs, path::Path};
+
+use bevy::{color::{Color, Srgba}, math::Vec3};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::parsing::{color_to_hex, AdjustableHull, BasePart, Part, Ship, Turret, XmlExtras};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum InterchangeFormat {
+    Json,
+    Ron,
+}
+
+impl InterchangeFormat {
+    pub fn of(path: &Path) -> Option<InterchangeFormat> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "json" => Some(InterchangeFormat::Json),
+            "ron" => Some(InterchangeFormat::Ron),
+            _ => None,
+        }
+    }
+
+    pub fn to_string<T: Serialize>(&self, value: &T) -> Result<String, Box<dyn Error>> {
+        match self {
+            InterchangeFormat::Json => Ok(serde_json::to_string_pretty(value)?),
+            InterchangeFormat::Ron => Ok(ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())?),
+        }
+    }
+
+    pub fn from_str<T: DeserializeOwned>(&self, text: &str) -> Result<T, Box<dyn Error>> {
+        match self {
+            InterchangeFormat::Json => Ok(serde_json::from_str(text)?),
+            InterchangeFormat::Ron => Ok(ron::from_str(text)?),
+        }
+    }
+}
+
+fn format_of(path: &Path) -> Result<InterchangeFormat, Box<dyn Error>> {
+    return InterchangeFormat::of(path).ok_or(format!("{:?} isn't a .json or .ron file", path).into());
+}
+
+pub fn save_parts(path: &Path, parts: &[Part]) -> Result<(), Box<dyn Error>> {
+    fs::write(path, format_of(path)?.to_string(&parts)?)?;
+    return Ok(());
+}
+
+/// Loads a parts file, or the parts out of a ship file
+pub fn load_parts(path: &Path) -> Result<Vec<Part>, Box<dyn Error>> {
+    let format = format_of(path)?;
+    let text = fs::read_to_string(path)?;
+    return match format.from_str::<Vec<Part>>(&text) {
+        Ok(parts) => Ok(parts),
+        Err(parts_error) => match format.from_str::<Ship>(&text) {
+            Ok(ship) => Ok(ship.parts),
+            Err(_) => Err(parts_error),
+        },
+    };
+}
+
+pub fn save_ship_document(path: &Path, ship: &Ship) -> Result<(), Box<dyn Error>> {
+    fs::write(path, format_of(path)?.to_string(ship)?)?;
+    return Ok(());
+}
+
+pub fn load_ship_document(path: &Path) -> Result<Ship, Box<dyn Error>> {
+    return format_of(path)?.from_str(&fs::read_to_string(path)?);
+}
+
+/// How a `Part` looks in json/ron, see the top of this file
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct PartDocument {
+    id: i32,
+    ignore_physics: bool,
+    position: Vec3,
+    rotation: Vec3,
+    scale: Vec3,
+    #[serde(with = "hex_color")]
+    color: Color,
+    armor: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    adjustable_hull: Option<AdjustableHull>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    turret: Option<Turret>,
+    #[serde(skip_serializing_if = "XmlExtras::is_empty")]
+    extras: XmlExtras,
+}
+
+impl Default for PartDocument {
+    fn default() -> Self {
+        PartDocument::from(Part::Normal(BasePart::default()))
+    }
+}
+
+impl From<Part> for PartDocument {
+    fn from(part: Part) -> Self {
+        let (base_part, adjustable_hull, turret) = part.to_optionals();
+        PartDocument {
+            id: base_part.id,
+            ignore_physics: base_part.ignore_physics,
+            position: base_part.position,
+            rotation: base_part.rotation,
+            scale: base_part.scale,
+            color: base_part.color,
+            armor: base_part.armor,
+            adjustable_hull: adjustable_hull.copied(),
+            turret: turret.copied(),
+            extras: base_part.extras.clone(),
+        }
+    }
+}
+
+impl From<PartDocument> for Part {
+    fn from(document: PartDocument) -> Self {
+        let base_part = BasePart {
+            id: document.id,
+            ignore_physics: document.ignore_physics,
+            position: document.position,
+            rotation: document.rotation,
+            scale: document.scale,
+            color: document.color,
+            armor: document.armor,
+            extras: document.extras,
+        };
+        if let Some(adjustable_hull) = document.adjustable_hull {
+            Part::AdjustableHull(base_part, adjustable_hull)
+        } else if let Some(turret) = document.turret {
+            Part::Turret(base_part, turret)
+        } else {
+            Part::Normal(base_part)
+        }
+    }
+}
+
+/// Colors as "#RRGGBB" like the game's hex, instead of bevy's color enum
+pub mod hex_color {
+    use super::*;
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("#{}", color_to_hex(color)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        Srgba::hex(&hex).map(Color::Srgba).map_err(serde::de::Error::custom)
+    }
+}
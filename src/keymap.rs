@@ -0,0 +1,291 @@
+//! Which keys run which commands, read from `keymap.ron` in the config directory
+//! (`~/.config/naval_sketch/keymap.ron` on linux), which is written with the default keys the first time.
+//!
+//! ```ron
+//! (
+//!     translation: {"w": smart_move_forward, "W": move_forward, "f": attributes_mode},
+//!     attributes: {"w": previous_attribute, " ": set_attribute},
+//!     rotation: {"w": pitch_forward, "W": local_pitch_forward},
+//!     operators: {"x": delete, "y": yank, "c": recolor, "=": align},
+//!     targets: {"w": forward, "g": connected, "*": same_id},
+//!     record_macro: "m",
+//!     replay_macro: "@",
+//! )
+//! ```
+//!
+//! A mode that's left out keeps its default keys, a mode that's there only has the keys it lists.
+//! Operators and targets are single characters used by the `[count]operator[count]target` grammar,
+//! the macro keys are single characters that work in every mode.
+//!
+//! Ctrl+S (save) and Ctrl+R (redo) aren't in the keymap, keys here are typed characters that build
+//! up a command and a chord never reaches the command line, so they stay fixed in `command_typing`.
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use enum_collections::{EnumMap, Enumerated};
+use serde::{Deserialize, Serialize};
+
+use crate::{command_grammar::Operator, editor::{CommandMode, CommandTree}};
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAction {
+    MoveForward,
+    MoveLeft,
+    MoveBack,
+    MoveRight,
+    MoveDown,
+    MoveUp,
+    SmartMoveForward,
+    SmartMoveLeft,
+    SmartMoveBack,
+    SmartMoveRight,
+    SmartMoveDown,
+    SmartMoveUp,
+
+    PitchForward,
+    PitchBack,
+    YawLeft,
+    YawRight,
+    RollLeft,
+    RollRight,
+    LocalPitchForward,
+    LocalPitchBack,
+    LocalYawLeft,
+    LocalYawRight,
+    LocalRollLeft,
+    LocalRollRight,
+
+    PreviousAttribute,
+    NextAttribute,
+    PreviousAttributeColumn,
+    NextAttributeColumn,
+    SetAttribute,
+
+    Paste,
+    Mirror,
+    Undo,
+    ToggleFloating,
+    CycleLanguage,
+    AttributesMode,
+    RotationMode,
+}
+
+/// What the key after an operator acts on
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetKey {
+    Forward,
+    Left,
+    Back,
+    Right,
+    Down,
+    Up,
+    Connected,
+    SameId,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keymap {
+    pub translation: BTreeMap<String, KeyAction>,
+    pub attributes: BTreeMap<String, KeyAction>,
+    pub rotation: BTreeMap<String, KeyAction>,
+    pub operators: BTreeMap<String, Operator>,
+    pub targets: BTreeMap<String, TargetKey>,
+    pub record_macro: String,
+    pub replay_macro: String,
+}
+
+fn bindings<T: Copy>(pairs: &[(&str, T)]) -> BTreeMap<String, T> {
+    return pairs.iter().map(|pair| (pair.0.to_owned(), pair.1)).collect();
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap {
+            translation: bindings(&[
+                ("W", KeyAction::MoveForward),
+                ("A", KeyAction::MoveLeft),
+                ("S", KeyAction::MoveBack),
+                ("D", KeyAction::MoveRight),
+                ("Q", KeyAction::MoveDown),
+                ("E", KeyAction::MoveUp),
+                ("w", KeyAction::SmartMoveForward),
+                ("a", KeyAction::SmartMoveLeft),
+                ("s", KeyAction::SmartMoveBack),
+                ("d", KeyAction::SmartMoveRight),
+                ("q", KeyAction::SmartMoveDown),
+                ("e", KeyAction::SmartMoveUp),
+                ("p", KeyAction::Paste),
+                ("M", KeyAction::Mirror),
+                ("f", KeyAction::AttributesMode),
+                ("F", KeyAction::ToggleFloating),
+                ("L", KeyAction::CycleLanguage),
+                ("u", KeyAction::Undo),
+                ("r", KeyAction::RotationMode),
+            ]),
+            attributes: bindings(&[
+                ("w", KeyAction::PreviousAttribute),
+                ("s", KeyAction::NextAttribute),
+                ("a", KeyAction::PreviousAttributeColumn),
+                ("d", KeyAction::NextAttributeColumn),
+                (" ", KeyAction::SetAttribute),
+                ("L", KeyAction::CycleLanguage),
+                ("u", KeyAction::Undo),
+            ]),
+            rotation: bindings(&[
+                ("w", KeyAction::PitchForward),
+                ("s", KeyAction::PitchBack),
+                ("a", KeyAction::YawLeft),
+                ("d", KeyAction::YawRight),
+                ("q", KeyAction::RollLeft),
+                ("e", KeyAction::RollRight),
+                ("W", KeyAction::LocalPitchForward),
+                ("S", KeyAction::LocalPitchBack),
+                ("A", KeyAction::LocalYawLeft),
+                ("D", KeyAction::LocalYawRight),
+                ("Q", KeyAction::LocalRollLeft),
+                ("E", KeyAction::LocalRollRight),
+                ("u", KeyAction::Undo),
+            ]),
+            operators: bindings(&[
+                ("x", Operator::Delete),
+                ("y", Operator::Yank),
+                ("c", Operator::Recolor),
+                ("=", Operator::Align),
+            ]),
+            targets: bindings(&[
+                ("w", TargetKey::Forward),
+                ("a", TargetKey::Left),
+                ("s", TargetKey::Back),
+                ("d", TargetKey::Right),
+                ("q", TargetKey::Down),
+                ("e", TargetKey::Up),
+                ("g", TargetKey::Connected),
+                ("*", TargetKey::SameId),
+            ]),
+            record_macro: "m".to_owned(),
+            replay_macro: "@".to_owned(),
+        }
+    }
+}
+
+/// Characters that mean something before any command, counts, repeating, ex lines and registers
+fn is_reserved(char: char) -> bool {
+    return char.is_ascii_digit() || char == '.' || char == ':' || char == '"';
+}
+
+impl Keymap {
+    pub fn path() -> Option<PathBuf> {
+        return Some(dirs::config_dir()?.join("naval_sketch").join("keymap.ron"));
+    }
+
+    /// Loads the keymap file, falling back to the default keys if it's broken
+    pub fn load() -> Keymap {
+        let Some(path) = Keymap::path() else { return Keymap::default(); };
+
+        let Ok(text) = fs::read_to_string(&path) else {
+            //give people a file to start editing from
+            let written = fs::create_dir_all(path.parent().unwrap()).and_then(|_| {
+                fs::write(&path, ron::ser::to_string_pretty(&Keymap::default(), ron::ser::PrettyConfig::default()).unwrap())
+            });
+            if let Err(error) = written {
+                println!("couldn't write the default keymap to {:?}: {:?}", path, error);
+            }
+            return Keymap::default();
+        };
+
+        let keymap: Keymap = match ron::from_str(&text) {
+            Ok(keymap) => keymap,
+            Err(error) => {
+                println!("couldn't read the keymap {:?}, using the default keys: {}", path, error);
+                return Keymap::default();
+            }
+        };
+        let problems = keymap.problems();
+        if !problems.is_empty() {
+            for problem in &problems {
+                println!("keymap {:?}: {}", path, problem);
+            }
+            println!("using the default keys until the keymap is fixed");
+            return Keymap::default();
+        }
+        return keymap;
+    }
+
+    pub fn bindings(&self, mode: CommandMode) -> Option<&BTreeMap<String, KeyAction>> {
+        match mode {
+            CommandMode::Translation => Some(&self.translation),
+            CommandMode::Attributes => Some(&self.attributes),
+            CommandMode::Rotation => Some(&self.rotation),
+            CommandMode::Disabled => None,
+        }
+    }
+
+    /// Bindings that can never be typed, because of what's typed before them
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        for (key, operator) in &self.operators {
+            if key.chars().count() != 1 || key.chars().any(is_reserved) {
+                problems.push(format!("operator {:?} ({}) has to be one character that isn't a digit, . : or \"", key, operator));
+            }
+        }
+        for (key, target) in &self.targets {
+            if key.chars().count() != 1 {
+                problems.push(format!("target {:?} ({:?}) has to be one character", key, target));
+            }
+            if self.operators.contains_key(key) {
+                problems.push(format!("target {:?} ({:?}) is also an operator, repeating an operator already means the selection", key, target));
+            }
+        }
+
+        for (name, key) in [("record_macro", &self.record_macro), ("replay_macro", &self.replay_macro)] {
+            if key.chars().count() != 1 || key.chars().any(is_reserved) {
+                problems.push(format!("{} {:?} has to be one character that isn't a digit, . : or \"", name, key));
+            }
+            if self.operators.contains_key(key) {
+                problems.push(format!("{} {:?} is also an operator", name, key));
+            }
+        }
+        if self.record_macro == self.replay_macro {
+            problems.push(format!("record_macro and replay_macro are both {:?}", self.record_macro));
+        }
+
+        for mode in CommandMode::VARIANTS {
+            let Some(bindings) = self.bindings(*mode) else { continue; };
+            for (key, action) in bindings {
+                if key.starts_with(self.record_macro.as_str()) || key.starts_with(self.replay_macro.as_str()) {
+                    problems.push(format!("{:?} {:?} ({:?}) starts with a macro key", mode, key, action));
+                }
+                if key.is_empty() || !key.chars().all(|char| char.is_ascii_alphabetic() || char == ' ') {
+                    problems.push(format!("{:?} {:?} ({:?}) can only be letters and spaces", mode, key, action));
+                }
+                //the shorter command runs as soon as it's typed
+                for (other_key, other_action) in bindings {
+                    if other_key != key && other_key.starts_with(key.as_str()) {
+                        problems.push(format!("{:?} {:?} ({:?}) is a prefix of {:?} ({:?})", mode, key, action, other_key, other_action));
+                    }
+                }
+                if *mode == CommandMode::Translation {
+                    if let Some(operator) = self.operators.iter().find(|operator| key.starts_with(operator.0.as_str())) {
+                        problems.push(format!("{:?} {:?} ({:?}) starts with the operator {:?} ({})", mode, key, action, operator.0, operator.1));
+                    }
+                }
+            }
+        }
+        return problems;
+    }
+
+    pub fn command_trees(&self) -> EnumMap<CommandMode, CommandTree, {CommandMode::SIZE}> {
+        let mut command_trees: EnumMap<CommandMode, CommandTree, {CommandMode::SIZE}> = EnumMap::new_default();
+        for mode in CommandMode::VARIANTS {
+            let Some(bindings) = self.bindings(*mode) else { continue; };
+            for (key, action) in bindings {
+                command_trees[*mode].add_command(key.as_bytes(), *action);
+            }
+        }
+        return command_trees;
+    }
+}
@@ -0,0 +1,95 @@
+//! Vim style macros. With the default keymap `ma` starts recording the commands typed into
+//! register `a`, `m` stops and `3@a` replays them three times. `q` is already the down movement
+//! so recording isn't on `q` like in vim, the keys can be changed in the keymap.
+//!
+//! Registers are saved to `macros.ron` in the data directory whenever a recording stops.
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use crate::{keymap::Keymap, registers::is_macro_register};
+
+/// How deep macros can replay other macros, so a macro that replays itself stops
+pub const MACRO_DEPTH_LIMIT: usize = 10;
+
+#[derive(Default)]
+pub struct Macros {
+    pub registers: BTreeMap<char, Vec<String>>,
+    //the register being recorded into and what's been typed so far
+    pub recording: Option<(char, Vec<String>)>,
+}
+
+pub enum MacroCommand {
+    Record(char),
+    StopRecording,
+    Replay {register: char, count: usize},
+}
+
+pub enum MacroParse {
+    NotMacro,
+    Complete(MacroCommand),
+    Incomplete,
+    Invalid,
+}
+
+impl Macros {
+    pub fn path() -> Option<PathBuf> {
+        return Some(dirs::data_dir()?.join("naval_sketch").join("macros.ron"));
+    }
+
+    pub fn load() -> Macros {
+        let mut macros = Macros::default();
+        let Some(path) = Macros::path() else { return macros; };
+        let Ok(text) = fs::read_to_string(&path) else { return macros; };
+        match ron::from_str(&text) {
+            Ok(registers) => macros.registers = registers,
+            Err(error) => println!("couldn't read the macros in {:?}: {}", path, error),
+        }
+        return macros;
+    }
+
+    pub fn save(&self) {
+        let Some(path) = Macros::path() else { return; };
+        let written = fs::create_dir_all(path.parent().unwrap()).and_then(|_| {
+            fs::write(&path, ron::ser::to_string_pretty(&self.registers, ron::ser::PrettyConfig::default()).unwrap())
+        });
+        if let Err(error) = written {
+            println!("couldn't save the macros to {:?}: {:?}", path, error);
+        }
+    }
+
+    /// Stops recording and keeps what was recorded
+    pub fn stop_recording(&mut self) {
+        let Some((register, commands)) = self.recording.take() else { return; };
+        println!("recorded {:?} commands into @{}", commands.len(), register);
+        self.registers.insert(register, commands);
+        self.save();
+    }
+}
+
+/// Recording and replaying commands, checked before any mode's own commands
+pub fn parse_macro_command(text: &str, keymap: &Keymap, recording: bool) -> MacroParse {
+    let digits = text.bytes().take_while(|byte| byte.is_ascii_digit()).count();
+    let mut rest = text[digits..].chars();
+    let Some(first) = rest.next() else { return MacroParse::NotMacro; };
+
+    if first.to_string() == keymap.replay_macro {
+        let Some(register) = rest.next() else { return MacroParse::Incomplete; };
+        if !is_macro_register(register) {
+            return MacroParse::Invalid;
+        }
+        let count = text[..digits].parse::<usize>().unwrap_or(1);
+        return MacroParse::Complete(MacroCommand::Replay { register, count });
+    }
+
+    if digits == 0 && first.to_string() == keymap.record_macro {
+        if recording {
+            return MacroParse::Complete(MacroCommand::StopRecording);
+        }
+        let Some(register) = rest.next() else { return MacroParse::Incomplete; };
+        if !is_macro_register(register) {
+            return MacroParse::Invalid;
+        }
+        return MacroParse::Complete(MacroCommand::Record(register));
+    }
+    return MacroParse::NotMacro;
+}
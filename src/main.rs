@@ -8,12 +8,30 @@ mod editor_utils;
 mod editor_actions;
 mod transform_gizmo_bevy;
 mod transform_gizmo;
+mod cli;
+mod interchange;
+mod ship_diff;
+mod ship_merge;
+mod command_grammar;
+mod ex_commands;
+mod keymap;
+mod macros;
+mod registers;
+mod placement;
+mod mirror;
+mod symmetry;
+mod ship_stats;
+mod hull_geometry;
+mod hydrostatics;
+mod stability;
+mod trim;
 
 use bevy::{color::Color, pbr::wireframe::{WireframeConfig, WireframePlugin}, prelude::*, reflect::List, render::{settings::{RenderCreation, WgpuFeatures, WgpuSettings}, RenderPlugin}, utils::HashMap};
 use bevy_egui::EguiPlugin;
 use cam_movement::CameraMovementPlugin;
 use editor::{EditorPlugin};
-use parsing::{load_save, AdjustableHull, BasePart, Part};
+use editor_ui::LoadProblems;
+use parsing::{load_save_partial, AdjustableHull, BasePart, Part, ShipHeader, XmlExtras};
 use parts::{on_part_meshes_init, place_part, register_all_parts, BasePartMesh, BasePartMeshes, PartRegistry};
 use transform_gizmo::GizmoVisuals;
 use transform_gizmo_bevy::{GizmoHotkeys, GizmoOptions, TransformGizmoPlugin};
@@ -176,23 +194,34 @@ fn setup(
 
     let path = init_data.file_path.clone();
 
-    let parts_result = load_save(Path::new(&path));
-
-
     println!("PLACING PARTS");
-    if let Ok(parts) = parts_result {
-        for part in parts {
-            let mut entity = commands.spawn_empty();
-            place_part(
-                &mut meshes,
-                &mut materials,
-                &asset_server,
-                &part_registry,
-                &mut entity,
-                &part);
+    if path != "test" {
+        match load_save_partial(Path::new(&path)) {
+            Ok((ship, problems)) => {
+                commands.insert_resource(ship.header);
+                for part in ship.parts {
+                    let mut entity = commands.spawn_empty();
+                    place_part(
+                        &mut meshes,
+                        &mut materials,
+                        &asset_server,
+                        &part_registry,
+                        &mut entity,
+                        &part);
+                }
+                for problem in &problems {
+                    println!("problem loading {}: {}",path,problem);
+                }
+                commands.insert_resource(LoadProblems { problems: problems.iter().map(|problem| problem.to_string()).collect() });
+            }
+            Err(error) => {
+                println!("couldn't load {}: {}",path,error);
+                commands.insert_resource(ShipHeader::default());
+                commands.insert_resource(LoadProblems { problems: vec![error.to_string()] });
+            }
         }
     }else{
-        println!("ERROR WAS {:?}",parts_result);
+        commands.insert_resource(ShipHeader::default());
         place_part(
                 &mut meshes,
                 &mut materials,
@@ -207,6 +236,7 @@ fn setup(
                     scale: Vec3 {x:5.0,y:1.0,z:5.0},
                     color: Color::WHITE,
                     armor: 0,
+                    extras: XmlExtras::default(),
                 }));
 
 
@@ -224,6 +254,7 @@ fn setup(
                     scale: Vec3 {x:1.0,y:1.0,z:1.0},
                     color: Color::srgb_u8(0, 255, 0),
                     armor: 0,
+                    extras: XmlExtras::default(),
                 }));
         
 
@@ -272,6 +303,10 @@ fn setup(
 fn main() {
     let args: Vec<String> = env::args().collect();
 
+    if let Some(exit_code) = cli::run(&args) {
+        std::process::exit(exit_code);
+    }
+
     let steam_path = &args[1];
     let file_path = &args[2];
 
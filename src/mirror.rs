@@ -0,0 +1,128 @@
+//! Mirroring parts across a plane, by default the ship's centreline (x = 0 in unity space).
+//!
+//! A mirror is a reflection, which no rotation can do on its own. Hulls and most models are
+//! symmetric across their own x, so that reflection is folded into the rotation and the part
+//! keeps its attributes. An adjustable hull lying across the plane is reflected along its
+//! length instead by swapping its front and back, which keeps its angles the way people build.
+
+use std::fmt::Display;
+
+use bevy::math::{Mat3, Quat, Vec3};
+
+use crate::{parsing::{AdjustableHull, Part}, parts::{bevy_quat_to_unity, unity_to_bevy_quat}};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MirrorPlane {
+    //0 x, 1 y, 2 z, in unity space
+    pub axis: usize,
+    pub offset: f32,
+}
+
+impl Default for MirrorPlane {
+    fn default() -> Self {
+        return MirrorPlane { axis: 0, offset: 0.0 };
+    }
+}
+
+impl Display for MirrorPlane {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"{}={}",["x","y","z"][self.axis],self.offset)
+    }
+}
+
+impl MirrorPlane {
+    /// Reads `[x|y|z] [offset]`, anything left out is the centreline
+    pub fn parse(text: &str) -> Result<MirrorPlane, String> {
+        let mut plane = MirrorPlane::default();
+        let mut words = text.split_whitespace();
+        if let Some(axis) = words.next() {
+            plane.axis = match axis.to_lowercase().as_str() {
+                "x" => 0,
+                "y" => 1,
+                "z" => 2,
+                _ => return Err(format!("unknown axis {:?}, it's x, y or z",axis)),
+            };
+        }
+        if let Some(offset) = words.next() {
+            plane.offset = offset.parse::<f32>().map_err(|_| format!("{:?} isn't a number",offset))?;
+        }
+        return Ok(plane);
+    }
+
+    /// How far a unity position is in front of the plane
+    pub fn distance(&self, position: &Vec3) -> f32 {
+        return position[self.axis] - self.offset;
+    }
+
+    pub fn mirror_position(&self, position: &Vec3) -> Vec3 {
+        let mut mirrored = *position;
+        mirrored[self.axis] = 2.0*self.offset - position[self.axis];
+        return mirrored;
+    }
+}
+
+fn reflection(axis: usize) -> Mat3 {
+    let mut diagonal = Vec3::ONE;
+    diagonal[axis] = -1.0;
+    return Mat3::from_diagonal(diagonal);
+}
+
+/// Whether flipping front and back gives the same shape mirrored along the length,
+/// only the front has a height scale and offset
+fn can_swap_ends(adjustable_hull: &AdjustableHull) -> bool {
+    return adjustable_hull.height_scale == 1.0 && adjustable_hull.height_offset == 0.0;
+}
+
+pub fn mirror_part(part: &Part, plane: &MirrorPlane) -> Part {
+    let mut mirrored = part.clone();
+
+    //unity to bevy only flips x, so a plane along an axis stays along the same axis
+    let rotation = Mat3::from_quat(unity_to_bevy_quat(&part.base_part().rotation));
+    let plane_reflection = reflection(plane.axis);
+    let local_normal = rotation.transpose()*Vec3::AXES[plane.axis];
+
+    let mut local_axis = 0;
+    if let Part::AdjustableHull(_, adjustable_hull) = &mut mirrored {
+        if local_normal.z.abs() > local_normal.x.abs() && can_swap_ends(adjustable_hull) {
+            local_axis = 2;
+            std::mem::swap(&mut adjustable_hull.front_width, &mut adjustable_hull.back_width);
+            std::mem::swap(&mut adjustable_hull.front_spread, &mut adjustable_hull.back_spread);
+        }
+    }
+
+    let mirrored_rotation = Quat::from_mat3(&(plane_reflection*rotation*reflection(local_axis)));
+    let base_part = mirrored.base_part_mut();
+    base_part.rotation = bevy_quat_to_unity(&mirrored_rotation);
+    base_part.position = plane.mirror_position(&base_part.position);
+    return mirrored;
+}
+
+fn roughly(a: f32, b: f32) -> bool {
+    return (a-b).abs() < 1e-4;
+}
+
+/// Whether two parts are the same up to float error, rotations compared as rotations
+pub fn roughly_equal(a: &Part, b: &Part) -> bool {
+    let (a_base, b_base) = (a.base_part(), b.base_part());
+    if a_base.id != b_base.id || a_base.color != b_base.color || a_base.armor != b_base.armor || a_base.ignore_physics != b_base.ignore_physics {
+        return false;
+    }
+    if a_base.position.distance(b_base.position) > 1e-3 || a_base.scale.distance(b_base.scale) > 1e-4 {
+        return false;
+    }
+    if unity_to_bevy_quat(&a_base.rotation).angle_between(unity_to_bevy_quat(&b_base.rotation)) > 1e-3 {
+        return false;
+    }
+    return match (a, b) {
+        (Part::Normal(_), Part::Normal(_)) => true,
+        (Part::Turret(_, a_turret), Part::Turret(_, b_turret)) => a_turret == b_turret,
+        (Part::AdjustableHull(_, a_hull), Part::AdjustableHull(_, b_hull)) => {
+            roughly(a_hull.length, b_hull.length) && roughly(a_hull.height, b_hull.height) &&
+            roughly(a_hull.front_width, b_hull.front_width) && roughly(a_hull.back_width, b_hull.back_width) &&
+            roughly(a_hull.front_spread, b_hull.front_spread) && roughly(a_hull.back_spread, b_hull.back_spread) &&
+            roughly(a_hull.top_roundness, b_hull.top_roundness) && roughly(a_hull.bottom_roundness, b_hull.bottom_roundness) &&
+            roughly(a_hull.height_scale, b_hull.height_scale) && roughly(a_hull.height_offset, b_hull.height_offset)
+        },
+        _ => false,
+    };
+}
@@ -1,23 +1,30 @@
 use core::str;
-use std::{error::Error, fmt::Display, fs, path::Path};
+use std::{error::Error, fmt::Display, fs, io::Write, path::Path, str::FromStr};
 
-use bevy::{color::Color, math::Vec3, prelude::Component};
-use quick_xml::{events::{BytesStart, Event}, Reader};
-use regex::Regex;
+use bevy::{color::{Color, ColorToPacked}, math::Vec3, prelude::{Component, Resource}};
+use quick_xml::{events::{BytesDecl, BytesStart, Event}, Reader, Writer};
+use serde::{Deserialize, Serialize};
 
-#[derive(Component, Debug, Copy, Clone)]
+use crate::interchange::{hex_color, PartDocument};
+
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BasePart {
     pub id: i32,
     pub ignore_physics: bool,
     pub position: Vec3,
     pub rotation: Vec3,
     pub scale: Vec3,
+    #[serde(with = "hex_color")]
     pub color: Color,
     pub armor: i32,
+    #[serde(skip_serializing_if = "XmlExtras::is_empty")]
+    pub extras: XmlExtras,
 }
 
-#[derive(Component, Debug, Copy, Clone)]
+#[derive(Component, Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 #[require(BasePart)]
+#[serde(default)]
 pub struct AdjustableHull {
     pub length: f32,
     pub height: f32,
@@ -31,10 +38,12 @@ pub struct AdjustableHull {
     pub height_offset: f32,
 }
 
-#[derive(Component, Debug, Copy, Clone)]
+#[derive(Component, Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 #[require(BasePart)]
+#[serde(default)]
 pub struct Turret{
     pub manual_control: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub elevator: Option<f32>,
 }
 
@@ -61,6 +70,7 @@ impl Default for BasePart {
             },
             color: Color::srgb(5.0, 5.0, 5.0),
             armor: 0,
+            extras: XmlExtras::default(),
         }
     }
 }
@@ -93,7 +103,8 @@ impl Default for Turret{
 }
 
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "PartDocument", from = "PartDocument")]
 pub enum Part {
     Normal(BasePart),
     AdjustableHull(BasePart,AdjustableHull),
@@ -118,11 +129,11 @@ impl Part{
 
     pub fn from_optionals(components: (&BasePart, Option<&AdjustableHull>, Option<&Turret>)) -> Part {
         if let Some(adjustable_hull) = components.1 {
-            Part::AdjustableHull(*components.0, *adjustable_hull)
+            Part::AdjustableHull(components.0.clone(), *adjustable_hull)
         } else if let Some(turret) = components.2 {
-            Part::Turret(*components.0, *turret)
+            Part::Turret(components.0.clone(), *turret)
         } else {
-            Part::Normal(*components.0)
+            Part::Normal(components.0.clone())
         }
     }
 
@@ -135,149 +146,689 @@ impl Part{
     }
 }
 
+/// Everything in an element that the editor doesn't model, kept so saving doesn't lose it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct XmlExtras {
+    pub attributes: Vec<(String,String)>,
+    //unknown attributes on child elements we do model, keyed by the child element's name
+    pub child_attributes: Vec<(String,Vec<(String,String)>)>,
+    //raw xml of child elements we don't model at all
+    pub elements: Vec<String>,
+}
+
+impl XmlExtras {
+    pub fn is_empty(&self) -> bool {
+        return self.attributes.is_empty() && self.child_attributes.is_empty() && self.elements.is_empty();
+    }
+    pub fn child_attributes(&self, element_name: &str) -> &[(String,String)] {
+        for pair in &self.child_attributes {
+            if pair.0 == element_name {
+                return &pair.1;
+            }
+        }
+        return &[];
+    }
+}
+
+/// The ship's own attributes, read from `<ship>` (or the root element when there's no `<ship>`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShipMetadata {
+    //empty strings aren't written
+    pub name: String,
+    pub game_version: String,
+    pub description: String,
+}
+
+impl ShipMetadata {
+    pub const NAME_KEY: &'static str = "name";
+    pub const GAME_VERSION_KEY: &'static str = "version";
+    pub const DESCRIPTION_KEY: &'static str = "description";
+
+    /// Moves the metadata attributes out of `attributes`
+    fn take_from(attributes: &mut Vec<(String,String)>) -> ShipMetadata {
+        let mut metadata = ShipMetadata::default();
+        attributes.retain(|(key, value)| {
+            match key.as_str() {
+                ShipMetadata::NAME_KEY => metadata.name = value.clone(),
+                ShipMetadata::GAME_VERSION_KEY => metadata.game_version = value.clone(),
+                ShipMetadata::DESCRIPTION_KEY => metadata.description = value.clone(),
+                _ => { return true; }
+            }
+            return false;
+        });
+        return metadata;
+    }
+
+    fn attributes(&self) -> impl Iterator<Item = (&str, &str)> {
+        return [
+            (ShipMetadata::NAME_KEY, self.name.as_str()),
+            (ShipMetadata::GAME_VERSION_KEY, self.game_version.as_str()),
+            (ShipMetadata::DESCRIPTION_KEY, self.description.as_str()),
+        ].into_iter().filter(|pair| !pair.1.is_empty());
+    }
+}
+
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShipHeader {
+    pub metadata: ShipMetadata,
+    pub root_name: String,
+    pub declaration: Option<String>,
+    pub root_extras: XmlExtras,
+    //None when the parts sit directly inside the root element
+    pub ship_extras: Option<XmlExtras>,
+}
+
+impl Default for ShipHeader {
+    fn default() -> ShipHeader {
+        ShipHeader {
+            metadata: ShipMetadata::default(),
+            root_name: "root".to_owned(),
+            declaration: None,
+            root_extras: XmlExtras::default(),
+            ship_extras: Some(XmlExtras::default()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Ship {
+    pub header: ShipHeader,
+    pub parts: Vec<Part>,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct TextPosition {
+    pub byte_offset: u64,
+    //both start at 1, 0 means the position isn't known
+    pub line: usize,
+    pub column: usize,
+}
+
+impl TextPosition {
+    pub fn of(text: &str, byte_offset: u64) -> TextPosition {
+        let before = &text.as_bytes()[..(byte_offset as usize).min(text.len())];
+        let line_start = before.iter().rposition(|byte| *byte == b'\n').map(|index| index+1).unwrap_or(0);
+        return TextPosition {
+            byte_offset,
+            line: before.iter().filter(|byte| **byte == b'\n').count()+1,
+            //counting the bytes that start a character
+            column: before[line_start..].iter().filter(|byte| (**byte & 0xC0) != 0x80).count()+1,
+        };
+    }
+}
+
+impl Display for TextPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.line == 0 {
+            write!(f,"unknown position")
+        } else {
+            write!(f,"line {} column {} (byte {})",self.line,self.column,self.byte_offset)
+        }
+    }
+}
+
 #[derive(Debug)]
-pub struct ParseError {
-    desc: String
+pub enum ParseError {
+    Io(std::io::Error),
+    Xml {
+        position: TextPosition,
+        part_index: Option<usize>,
+        error: quick_xml::Error,
+    },
+    MissingAttribute {
+        position: TextPosition,
+        part_index: Option<usize>,
+        element: String,
+        attribute: String,
+    },
+    InvalidAttribute {
+        position: TextPosition,
+        part_index: Option<usize>,
+        element: String,
+        attribute: String,
+        value: String,
+        reason: String,
+    },
+    UnclosedPart {
+        position: TextPosition,
+        part_index: usize,
+    },
+}
+
+impl ParseError {
+    pub fn position(&self) -> Option<TextPosition> {
+        match self {
+            ParseError::Io(_) => None,
+            ParseError::Xml { position, .. } => Some(*position),
+            ParseError::MissingAttribute { position, .. } => Some(*position),
+            ParseError::InvalidAttribute { position, .. } => Some(*position),
+            ParseError::UnclosedPart { position, .. } => Some(*position),
+        }
+    }
+    pub fn part_index(&self) -> Option<usize> {
+        match self {
+            ParseError::Io(_) => None,
+            ParseError::Xml { part_index, .. } => *part_index,
+            ParseError::MissingAttribute { part_index, .. } => *part_index,
+            ParseError::InvalidAttribute { part_index, .. } => *part_index,
+            ParseError::UnclosedPart { part_index, .. } => Some(*part_index),
+        }
+    }
 }
+
 impl Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f,"{}",self.desc)
+        if let ParseError::Io(error) = self {
+            return write!(f,"couldn't read file: {}",error);
+        }
+        write!(f,"{}",self.position().unwrap_or_default())?;
+        if let Some(part_index) = self.part_index() {
+            write!(f,", part #{}",part_index)?;
+        }
+        match self {
+            ParseError::Io(_) => Ok(()),
+            ParseError::Xml { error, .. } => write!(f,": malformed xml: {}",error),
+            ParseError::MissingAttribute { element, attribute, .. } => write!(f,": <{}> is missing attribute {:?}",element,attribute),
+            ParseError::InvalidAttribute { element, attribute, value, reason, .. } => write!(f,": <{}> has invalid {}={:?} ({})",element,attribute,value,reason),
+            ParseError::UnclosedPart { .. } => write!(f,": <part> is never closed"),
+        }
     }
 }
 
 impl std::error::Error for ParseError {}
 
+impl From<std::io::Error> for ParseError {
+    fn from(error: std::io::Error) -> Self {
+        ParseError::Io(error)
+    }
+}
+
+/// Where the parser currently is, used to give errors a location.
+/// Only the byte offset is kept, the line and column are counted once an error needs them
+#[derive(Copy, Clone)]
+struct ErrorContext<'a> {
+    //None when there's no text to count lines in
+    text: Option<&'a str>,
+    byte_offset: u64,
+    part_index: Option<usize>,
+}
+
+impl ErrorContext<'_> {
+    fn position(&self) -> TextPosition {
+        return match self.text {
+            Some(text) => TextPosition::of(text, self.byte_offset),
+            None => TextPosition::default(),
+        };
+    }
+    fn xml(&self, error: quick_xml::Error) -> ParseError {
+        ParseError::Xml { position: self.position(), part_index: self.part_index, error }
+    }
+    fn missing(&self, e: &BytesStart, attribute: &str) -> ParseError {
+        ParseError::MissingAttribute {
+            position: self.position(),
+            part_index: self.part_index,
+            element: String::from_utf8_lossy(e.name().as_ref()).to_string(),
+            attribute: attribute.to_owned(),
+        }
+    }
+    fn invalid(&self, e: &BytesStart, attribute: &str, value: &str, reason: String) -> ParseError {
+        ParseError::InvalidAttribute {
+            position: self.position(),
+            part_index: self.part_index,
+            element: String::from_utf8_lossy(e.name().as_ref()).to_string(),
+            attribute: attribute.to_owned(),
+            value: value.to_owned(),
+            reason,
+        }
+    }
+}
+
 pub fn get_attribute_string<'a>(e: &'a BytesStart<'a>, field_name: &str) -> Result<String, Box<dyn std::error::Error>> {
     //println!("checking the {:?} which was {:?}", field_name, str::from_utf8(e.try_get_attribute(field_name)?.unwrap().value.as_ref()));
+    let context = ErrorContext { text: None, byte_offset: 0, part_index: None };
+
+    return Ok(attribute_string(e, field_name, &context)?);
+}
+
+fn attribute_string(e: &BytesStart, attribute: &str, context: &ErrorContext) -> Result<String, ParseError> {
+    match e.try_get_attribute(attribute) {
+        Ok(Some(value)) => Ok(value.unescape_value().map_err(|error| context.xml(error))?.to_string()),
+        Ok(None) => Err(context.missing(e, attribute)),
+        Err(error) => Err(context.xml(error.into())),
+    }
+}
+
+fn parse_attribute<T: FromStr>(e: &BytesStart, attribute: &str, context: &ErrorContext) -> Result<T, ParseError> where T::Err: Display {
+    let value = attribute_string(e, attribute, context)?;
+    return value.parse::<T>().map_err(|error| context.invalid(e, attribute, &value, error.to_string()));
+}
 
-    return Ok(str::from_utf8(e.try_get_attribute(field_name)?
-        .ok_or(ParseError{desc: format!("field {:?} missing from {:?}",field_name,e).to_string()})?
-        .value.as_ref())?.to_string());
+fn parse_bool_attribute(e: &BytesStart, attribute: &str, context: &ErrorContext) -> Result<bool, ParseError> {
+    let value = attribute_string(e, attribute, context)?;
+    return value.to_lowercase().parse::<bool>().map_err(|error| context.invalid(e, attribute, &value, error.to_string()));
 }
 
-pub fn load_save(file_path: &Path) -> Result<Vec<Part>, Box<dyn Error>> {
-    //let xml = fs::read_to_string(&file_path).expect("Should have been able to read the file");
+fn parse_vec3_attributes(e: &BytesStart, context: &ErrorContext) -> Result<Vec3, ParseError> {
+    return Ok(Vec3 {
+        x: parse_attribute(e, "x", context)?,
+        y: parse_attribute(e, "y", context)?,
+        z: parse_attribute(e, "z", context)?,
+    });
+}
+
+/// Loads a ship, failing on the first problem found
+pub fn load_save(file_path: &Path) -> Result<Ship, ParseError> {
     let xml = fs::read_to_string(&file_path)?;
+    let (ship, mut problems) = parse_ship(&xml, false);
+    if problems.is_empty() {
+        return Ok(ship);
+    }
+    return Err(problems.remove(0));
+}
 
+/// Loads a ship, skipping any `<part>` that can't be read and returning what went wrong alongside it
+pub fn load_save_partial(file_path: &Path) -> Result<(Ship, Vec<ParseError>), ParseError> {
+    let xml = fs::read_to_string(&file_path)?;
+    return Ok(parse_ship(&xml, true));
+}
+
+/// Parses ship xml, stops at the first problem unless `recover` is set
+pub fn parse_ship(xml: &str, recover: bool) -> (Ship, Vec<ParseError>) {
     //println!("thing is {xml}");
 
-    let mut reader = Reader::from_str(xml.as_str());
+    let mut reader = Reader::from_str(xml);
     //reader.config_mut().trim_text(true);
 
-    let mut parts: Vec<Part> = Vec::new();
+    let mut ship = Ship::default();
+    ship.header.ship_extras = None;
+    let mut problems: Vec<ParseError> = Vec::new();
 
     let mut current_part: Part = Part::Normal(BasePart::default());
-    let re = Regex::new(r"([^A-Fa-f0-9])").unwrap();
+    let mut part_count: usize = 0;
+    let mut part_start: u64 = 0;
+    let mut in_root = false;
+    let mut in_ship = false;
+    let mut in_part = false;
+    //set when the current part had an error and is being skipped
+    let mut skipping_part = false;
 
     // The `Reader` does not implement `Iterator` because it outputs borrowed data (`Cow`s)
     loop {
-        // NOTE: this is the generic case when we don't know about the input BufRead.
-        // when the input is a &str or a &[u8], we don't actually need to use another
-        // buffer, we could directly call `reader.read_event()`
-
-        match reader.read_event() {
-            Err(e) => panic!("Error at position {}: {:?}", reader.error_position(), e),
+        let mut context = ErrorContext {
+            text: Some(xml),
+            byte_offset: reader.buffer_position(),
+            part_index: if in_part {Some(part_count-1)} else {None},
+        };
+
+        let result: Result<(), ParseError> = match reader.read_event() {
+            Err(e) => {
+                context.byte_offset = reader.error_position();
+                problems.push(context.xml(e));
+                //the reader can't be trusted to continue after malformed xml
+                break;
+            },
             // exits the loop when reaching end of file
             Ok(Event::Eof) => {
+                if in_part {
+                    problems.push(ParseError::UnclosedPart { position: TextPosition::of(xml, part_start), part_index: part_count-1 });
+                }
                 break;
             },
 
-            Ok(Event::Start(e)) => {
+            Ok(Event::Decl(e)) => {
+                ship.header.declaration = Some(String::from_utf8_lossy(&e).to_string());
+                Ok(())
+            }
+
+            Ok(Event::Start(e)) => (|| -> Result<(), ParseError> {
+                if !in_root {
+                    in_root = true;
+                    ship.header.root_name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                    ship.header.root_extras.attributes = unknown_attributes(&e, &[], &context)?;
+                    return Ok(());
+                }
+                if in_part {
+                    if skipping_part || !read_part_child(&mut current_part, &e, &context)? {
+                        let inner = reader.read_text(e.name()).map_err(|error| context.xml(error))?;
+                        current_part.base_part_mut().extras.elements.push(
+                            format!("<{}>{}</{}>", String::from_utf8_lossy(&e), inner, String::from_utf8_lossy(e.name().as_ref()))
+                        );
+                    }
+                    return Ok(());
+                }
                 match e.name().as_ref() {
                     b"part" => {
+                        in_part = true;
+                        part_count += 1;
+                        part_start = context.byte_offset;
+                        context.part_index = Some(part_count-1);
                         for attribute_result in e.attributes() {
-                            let attribute = attribute_result?;
+                            let attribute = attribute_result.map_err(|error| context.xml(error.into()))?;
                             match attribute.key.local_name().as_ref() {
                                 b"id" => {
-                                    current_part.base_part_mut().id = str::from_utf8(attribute.value.as_ref())?
-                                        .parse::<i32>()?;
+                                    current_part.base_part_mut().id = parse_attribute(&e, "id", &context)?;
                                 }
                                 b"ignorePhysics" => {
-                                    current_part.base_part_mut().ignore_physics = str::from_utf8(attribute.value.as_ref())?.to_lowercase().parse::<bool>()?
+                                    current_part.base_part_mut().ignore_physics = parse_bool_attribute(&e, "ignorePhysics", &context)?;
+                                }
+                                _ => {
+                                    current_part.base_part_mut().extras.attributes.push((
+                                        String::from_utf8_lossy(attribute.key.as_ref()).to_string(),
+                                        attribute.unescape_value().map_err(|error| context.xml(error))?.to_string()
+                                    ));
                                 }
-                                _ => {}
                             }
                         }
                     }
+                    b"ship" if !in_ship => {
+                        in_ship = true;
+                        ship.header.ship_extras = Some(XmlExtras {
+                            attributes: unknown_attributes(&e, &[], &context)?,
+                            ..XmlExtras::default()
+                        });
+                    }
                     _ => {
+                        let inner = reader.read_text(e.name()).map_err(|error| context.xml(error))?;
+                        header_extras(&mut ship.header, in_ship).elements.push(
+                            format!("<{}>{}</{}>", String::from_utf8_lossy(&e), inner, String::from_utf8_lossy(e.name().as_ref()))
+                        );
                     }
                 }
-            }
-            Ok(Event::Empty(e)) => {
-                match e.name().as_ref() {
-                    b"data" => {
-                        if let Part::Normal(base_part) = current_part {
-                            current_part = Part::AdjustableHull(base_part, AdjustableHull::default());
-                        }
-                        if let Part::AdjustableHull(_,adjustable_hull) = &mut current_part {
-                            adjustable_hull.length= get_attribute_string(&e, "length")?.parse::<f32>()?;
-                            adjustable_hull.height= get_attribute_string(&e, "height")?.parse::<f32>()?;
-                            adjustable_hull.front_width= get_attribute_string(&e, "frontWidth")?.parse::<f32>()?;
-                            adjustable_hull.back_width= get_attribute_string(&e, "backWidth")?.parse::<f32>()?;
-                            adjustable_hull.front_spread= get_attribute_string(&e, "frontSpread")?.parse::<f32>()?;
-                            adjustable_hull.back_spread= get_attribute_string(&e, "backSpread")?.parse::<f32>()?;
-                            adjustable_hull.top_roundness= get_attribute_string(&e, "upCurve")?.parse::<f32>()?;
-                            adjustable_hull.bottom_roundness= get_attribute_string(&e, "downCurve")?.parse::<f32>()?;
-                            adjustable_hull.height_scale= get_attribute_string(&e, "heightScale")?.parse::<f32>()?;
-                            adjustable_hull.height_offset= get_attribute_string(&e, "heightOffset")?.parse::<f32>()?;
-                        }
-                    }
-
-                    b"turret" => {
-                        if let Part::Normal(base_part) = current_part {
-                            current_part = Part::Turret(base_part, Turret::default());
-                        }
-                        if let Part::Turret(_,turret) = &mut current_part {
-                            turret.manual_control = get_attribute_string(&e, "manualControl")?.to_lowercase().parse::<bool>()?;
-                            if let Ok(elevator_string) = get_attribute_string(&e, "evevator"){
-                                turret.elevator = Some(elevator_string.parse::<f32>()?);
-                            }
-                        }
-                    }
-                    b"position" => {
-                        current_part.base_part_mut().position.x = get_attribute_string(&e, "x")?.parse::<f32>()?;
-                        current_part.base_part_mut().position.y = get_attribute_string(&e, "y")?.parse::<f32>()?;
-                        current_part.base_part_mut().position.z = get_attribute_string(&e, "z")?.parse::<f32>()?;
-                    }
-                    b"rotation" => {
-                        current_part.base_part_mut().rotation.x = get_attribute_string(&e, "x")?.parse::<f32>()?;
-                        current_part.base_part_mut().rotation.y = get_attribute_string(&e, "y")?.parse::<f32>()?;
-                        current_part.base_part_mut().rotation.z = get_attribute_string(&e, "z")?.parse::<f32>()?;
-                    }
-                    b"scale" => {
-                        current_part.base_part_mut().scale.x = get_attribute_string(&e, "x")?.parse::<f32>()?;
-                        current_part.base_part_mut().scale.y = get_attribute_string(&e, "y")?.parse::<f32>()?;
-                        current_part.base_part_mut().scale.z = get_attribute_string(&e, "z")?.parse::<f32>()?;
+                return Ok(());
+            })(),
+            Ok(Event::Empty(e)) => (|| -> Result<(), ParseError> {
+                if in_part {
+                    if skipping_part || !read_part_child(&mut current_part, &e, &context)? {
+                        current_part.base_part_mut().extras.elements.push(format!("<{}/>", String::from_utf8_lossy(&e)));
                     }
-                    b"color" => {
-                        let color: u32 = u32::from_str_radix(re.replace_all(&get_attribute_string(&e, "hex")?,"").as_ref(), 16)?;
-                        current_part.base_part_mut().color = Color::srgb_u8((color >> 16) as u8,(color >> 8) as u8,(color >> 0) as u8);
-                    }
-                    _ => {}
+                } else if in_root {
+                    header_extras(&mut ship.header, in_ship).elements.push(format!("<{}/>", String::from_utf8_lossy(&e)));
                 }
-
-                
-
-
-
-            }
+                return Ok(());
+            })(),
             Ok(Event::End(e)) => {
                 //println!("the end is {:?}",e.name().as_ref());
                 match e.name().as_ref() {
-                    b"part" => {
-                        parts.push(current_part);
+                    b"part" if in_part => {
+                        in_part = false;
+                        if !skipping_part {
+                            ship.parts.push(current_part);
+                        }
+                        skipping_part = false;
                         current_part = Part::Normal(BasePart::default());
                     }
+                    b"ship" if in_ship && !in_part => {
+                        in_ship = false;
+                    }
                     _ => {}
                 }
+                Ok(())
             }
 
             // There are several other `Event`s we do not consider here
-            _ => (),
+            _ => Ok(()),
+        };
+
+        if let Err(problem) = result {
+            problems.push(problem);
+            if !recover || !in_part {
+                break;
+            }
+            skipping_part = true;
         }
         // if we don't keep a borrow elsewhere, we can clear the buffer to keep memory usage low
         //buf.clear();
     }
 
-    return Ok(parts);
+    let metadata_attributes = match ship.header.ship_extras.as_mut() {
+        Some(ship_extras) => &mut ship_extras.attributes,
+        None => &mut ship.header.root_extras.attributes,
+    };
+    ship.header.metadata = ShipMetadata::take_from(metadata_attributes);
+
+    return (ship, problems);
+}
+
+/// Parts out of a whole save or just `<part>` elements, like copied ones, skipping broken parts
+pub fn parse_parts(xml: &str) -> (Vec<Part>, Vec<ParseError>) {
+    if xml.trim_start().starts_with("<part") {
+        //the first element is taken as the root, so bare parts need one
+        let (ship, problems) = parse_ship(&format!("<root>{}</root>", xml), true);
+        return (ship.parts, problems);
+    }
+    let (ship, problems) = parse_ship(xml, true);
+    return (ship.parts, problems);
+}
+
+fn header_extras(header: &mut ShipHeader, in_ship: bool) -> &mut XmlExtras {
+    if in_ship {
+        if let Some(ship_extras) = header.ship_extras.as_mut() {
+            return ship_extras;
+        }
+    }
+    return &mut header.root_extras;
+}
+
+fn unknown_attributes(e: &BytesStart, known: &[&str], context: &ErrorContext) -> Result<Vec<(String,String)>, ParseError> {
+    let mut attributes = Vec::new();
+    for attribute_result in e.attributes() {
+        let attribute = attribute_result.map_err(|error| context.xml(error.into()))?;
+        let key = String::from_utf8_lossy(attribute.key.as_ref()).to_string();
+        if known.contains(&key.as_str()) { continue; }
+        attributes.push((key, attribute.unescape_value().map_err(|error| context.xml(error))?.to_string()));
+    }
+    return Ok(attributes);
+}
+
+/// Reads a child element of `<part>` into the part, returns false if it's an element we don't model
+fn read_part_child(current_part: &mut Part, e: &BytesStart, context: &ErrorContext) -> Result<bool, ParseError> {
+    let known: &[&str] = match e.name().as_ref() {
+        b"data" => {
+            if let Part::Normal(base_part) = current_part {
+                *current_part = Part::AdjustableHull(base_part.clone(), AdjustableHull::default());
+            }
+            if let Part::AdjustableHull(_,adjustable_hull) = current_part {
+                adjustable_hull.length = parse_attribute(e, "length", context)?;
+                adjustable_hull.height = parse_attribute(e, "height", context)?;
+                adjustable_hull.front_width = parse_attribute(e, "frontWidth", context)?;
+                adjustable_hull.back_width = parse_attribute(e, "backWidth", context)?;
+                adjustable_hull.front_spread = parse_attribute(e, "frontSpread", context)?;
+                adjustable_hull.back_spread = parse_attribute(e, "backSpread", context)?;
+                adjustable_hull.top_roundness = parse_attribute(e, "upCurve", context)?;
+                adjustable_hull.bottom_roundness = parse_attribute(e, "downCurve", context)?;
+                adjustable_hull.height_scale = parse_attribute(e, "heightScale", context)?;
+                adjustable_hull.height_offset = parse_attribute(e, "heightOffset", context)?;
+            }
+            &["length","height","frontWidth","backWidth","frontSpread","backSpread","upCurve","downCurve","heightScale","heightOffset"]
+        }
+
+        b"turret" => {
+            if let Part::Normal(base_part) = current_part {
+                *current_part = Part::Turret(base_part.clone(), Turret::default());
+            }
+            if let Part::Turret(_,turret) = current_part {
+                turret.manual_control = parse_bool_attribute(e, "manualControl", context)?;
+                if e.try_get_attribute("evevator").map_err(|error| context.xml(error.into()))?.is_some() {
+                    turret.elevator = Some(parse_attribute(e, "evevator", context)?);
+                }
+            }
+            &["manualControl","evevator"]
+        }
+        b"position" => {
+            current_part.base_part_mut().position = parse_vec3_attributes(e, context)?;
+            &["x","y","z"]
+        }
+        b"rotation" => {
+            current_part.base_part_mut().rotation = parse_vec3_attributes(e, context)?;
+            &["x","y","z"]
+        }
+        b"scale" => {
+            current_part.base_part_mut().scale = parse_vec3_attributes(e, context)?;
+            &["x","y","z"]
+        }
+        b"color" => {
+            let hex = attribute_string(e, "hex", context)?;
+            let digits: String = hex.chars().filter(|char| char.is_ascii_hexdigit()).collect();
+            let color = u32::from_str_radix(&digits, 16).map_err(|error| context.invalid(e, "hex", &hex, error.to_string()))?;
+            current_part.base_part_mut().color = Color::srgb_u8((color >> 16) as u8,(color >> 8) as u8,(color >> 0) as u8);
+            &["hex"]
+        }
+        b"armor" => {
+            current_part.base_part_mut().armor = parse_attribute(e, "value", context)?;
+            &["value"]
+        }
+        _ => { return Ok(false); }
+    };
+
+    let unknown = unknown_attributes(e, known, context)?;
+    if !unknown.is_empty() {
+        current_part.base_part_mut().extras.child_attributes.push((String::from_utf8_lossy(e.name().as_ref()).to_string(), unknown));
+    }
+    return Ok(true);
+}
+
+
+pub fn save_ship(file_path: &Path, ship: &Ship) -> Result<(), Box<dyn Error>> {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    let header = &ship.header;
+
+    if let Some(declaration) = &header.declaration {
+        writer.write_event(Event::Decl(BytesDecl::from_start(BytesStart::from_content(declaration.as_str(), 3))))?;
+    }
+
+    let root_metadata = if header.ship_extras.is_none() { Some(&header.metadata) } else { None };
+    writer.create_element(header.root_name.as_str())
+        .with_attributes(root_metadata.into_iter().flat_map(|metadata| metadata.attributes()))
+        .with_attributes(header.root_extras.attributes.iter().map(|pair| (pair.0.as_str(), pair.1.as_str())))
+        .write_inner_content(|writer| {
+            for element in &header.root_extras.elements {
+                write_raw(writer, element)?;
+            }
+            if let Some(ship_extras) = &header.ship_extras {
+                writer.create_element("ship")
+                    .with_attributes(header.metadata.attributes())
+                    .with_attributes(ship_extras.attributes.iter().map(|pair| (pair.0.as_str(), pair.1.as_str())))
+                    .write_inner_content(|writer| {
+                        for element in &ship_extras.elements {
+                            write_raw(writer, element)?;
+                        }
+                        for part in &ship.parts {
+                            write_part(writer, part)?;
+                        }
+                        return Ok(());
+                    })?;
+            } else {
+                for part in &ship.parts {
+                    write_part(writer, part)?;
+                }
+            }
+            return Ok(());
+        })?;
+
+    fs::write(file_path, writer.into_inner())?;
+    return Ok(());
+}
+
+/// Just the `<part>` elements, for copying parts as text
+pub fn parts_to_xml(parts: &[Part]) -> String {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    for part in parts {
+        //writing to a vec can't fail
+        write_part(&mut writer, part).unwrap();
+    }
+    return String::from_utf8(writer.into_inner()).unwrap();
+}
+
+fn write_part<W: Write>(writer: &mut Writer<W>, part: &Part) -> std::io::Result<()> {
+    let base_part = part.base_part();
+    let extras = &base_part.extras;
+
+    writer.create_element("part")
+        .with_attribute(("id", base_part.id.to_string().as_str()))
+        .with_attribute(("ignorePhysics", bool_to_string(base_part.ignore_physics)))
+        .with_attributes(extras.attributes.iter().map(|pair| (pair.0.as_str(), pair.1.as_str())))
+        .write_inner_content(|writer| {
+            if let Part::AdjustableHull(_, adjustable_hull) = part {
+                writer.create_element("data")
+                    .with_attribute(("length", adjustable_hull.length.to_string().as_str()))
+                    .with_attribute(("height", adjustable_hull.height.to_string().as_str()))
+                    .with_attribute(("frontWidth", adjustable_hull.front_width.to_string().as_str()))
+                    .with_attribute(("backWidth", adjustable_hull.back_width.to_string().as_str()))
+                    .with_attribute(("frontSpread", adjustable_hull.front_spread.to_string().as_str()))
+                    .with_attribute(("backSpread", adjustable_hull.back_spread.to_string().as_str()))
+                    .with_attribute(("upCurve", adjustable_hull.top_roundness.to_string().as_str()))
+                    .with_attribute(("downCurve", adjustable_hull.bottom_roundness.to_string().as_str()))
+                    .with_attribute(("heightScale", adjustable_hull.height_scale.to_string().as_str()))
+                    .with_attribute(("heightOffset", adjustable_hull.height_offset.to_string().as_str()))
+                    .with_attributes(extra_child_attributes(extras, "data"))
+                    .write_empty()?;
+            }
+            if let Part::Turret(_, turret) = part {
+                let mut element = writer.create_element("turret")
+                    .with_attribute(("manualControl", bool_to_string(turret.manual_control)));
+                if let Some(elevator) = turret.elevator {
+                    //the game spells it like this
+                    element = element.with_attribute(("evevator", elevator.to_string().as_str()));
+                }
+                element.with_attributes(extra_child_attributes(extras, "turret")).write_empty()?;
+            }
+
+            write_vec3(writer, "position", &base_part.position, extras)?;
+            write_vec3(writer, "rotation", &base_part.rotation, extras)?;
+            write_vec3(writer, "scale", &base_part.scale, extras)?;
+            writer.create_element("color")
+                .with_attribute(("hex", color_to_hex(&base_part.color).as_str()))
+                .with_attributes(extra_child_attributes(extras, "color"))
+                .write_empty()?;
+            writer.create_element("armor")
+                .with_attribute(("value", base_part.armor.to_string().as_str()))
+                .with_attributes(extra_child_attributes(extras, "armor"))
+                .write_empty()?;
+
+            for element in &extras.elements {
+                write_raw(writer, element)?;
+            }
+            return Ok(());
+        })?;
+    return Ok(());
+}
+
+fn write_vec3<W: Write>(writer: &mut Writer<W>, name: &str, vec: &Vec3, extras: &XmlExtras) -> std::io::Result<()> {
+    writer.create_element(name)
+        .with_attribute(("x", vec.x.to_string().as_str()))
+        .with_attribute(("y", vec.y.to_string().as_str()))
+        .with_attribute(("z", vec.z.to_string().as_str()))
+        .with_attributes(extra_child_attributes(extras, name))
+        .write_empty()?;
+    return Ok(());
+}
+
+fn extra_child_attributes<'a>(extras: &'a XmlExtras, element_name: &str) -> impl Iterator<Item = (&'a str, &'a str)> {
+    extras.child_attributes(element_name).iter().map(|pair| (pair.0.as_str(), pair.1.as_str()))
+}
+
+/// Writes xml we kept verbatim back out, going through the writer so it gets indented like everything else
+fn write_raw<W: Write>(writer: &mut Writer<W>, raw: &str) -> std::io::Result<()> {
+    let mut reader = Reader::from_str(raw);
+    reader.config_mut().trim_text(true);
+    loop {
+        match reader.read_event().map_err(std::io::Error::other)? {
+            Event::Eof => break,
+            event => writer.write_event(event)?,
+        }
+    }
+    return Ok(());
+}
+
+fn bool_to_string(value: bool) -> &'static str {
+    if value {"True"} else {"False"}
+}
+
+pub fn color_to_hex(color: &Color) -> String {
+    let [r, g, b, _] = color.to_srgba().to_u8_array();
+    return format!("{:02X}{:02X}{:02X}", r, g, b);
 }
@@ -1,4 +1,4 @@
-use std::{fmt::Display, iter::once, ops::Deref, path::Path};
+use std::{error::Error, fmt::Display, iter::once, ops::Deref, path::Path};
 use bevy::{asset::{AssetPath, RenderAssetUsages}, hierarchy::HierarchyEvent, log::tracing_subscriber::filter::combinator::And, prelude::*, reflect::List, render::{mesh::Indices, view::RenderLayers}, utils::HashMap};
 use dirs::cache_dir;
 use enum_collections::{EnumMap, Enumerated};
@@ -68,25 +68,33 @@ pub struct PartData {
 pub struct WeaponData {
 }
 
+impl PartRegistry {
+    /// Reads every builtin and workshop part out of a steam install, extracting into the cache folder as needed
+    pub fn load(steam_folder: &Path) -> Result<PartRegistry, Box<dyn Error>> {
+        let mut part_registry = PartRegistry { parts: HashMap::new() };
+        let cache_folder = cache_dir().ok_or("there's no cache directory to extract the parts into")?.join("naval_sketch");
+        let workshop_folder = steam_folder.join("steamapps").join("workshop").join("content").join("842780");
+        let game_folder = steam_folder.join("steamapps").join("common").join("NavalArt");
+        create_dir_all(&cache_folder)?;
+
+        let workshop_parts = get_workshop_parts(&workshop_folder, &cache_folder);
+        for workshop_port in workshop_parts {
+            part_registry.parts.insert(workshop_port.id,workshop_port);
+        }
+
+        let builtin_parts = get_builtin_parts(&game_folder, &cache_folder);
+        for builtin_part in builtin_parts {
+            part_registry.parts.insert(builtin_part.id,builtin_part);
+        }
+        return Ok(part_registry);
+    }
+}
+
 pub fn register_all_parts(
     init_data: Res<InitData>,
     mut part_registry: ResMut<PartRegistry>
 ){
-    let cache_folder = cache_dir().unwrap().join("naval_sketch");
-    let steam_folder = PathBuf::from(init_data.steam_path.clone());
-    let workshop_folder = steam_folder.join("steamapps").join("workshop").join("content").join("842780");
-    let game_folder = steam_folder.join("steamapps").join("common").join("NavalArt");
-    create_dir_all(&cache_folder).unwrap();
-
-    let workshop_parts = get_workshop_parts(&workshop_folder, &cache_folder);
-    for workshop_port in workshop_parts {
-        part_registry.parts.insert(workshop_port.id,workshop_port);
-    }
-
-    let builtin_parts = get_builtin_parts(&game_folder, &cache_folder);
-    for builtin_part in builtin_parts {
-        part_registry.parts.insert(builtin_part.id,builtin_part);
-    }
+    *part_registry = PartRegistry::load(&PathBuf::from(init_data.steam_path.clone())).unwrap_or_else(|error| panic!("failed to load the parts: {}", error));
 
     println!("all registered parts is {:?}",part_registry.parts.keys());
 
@@ -112,8 +120,52 @@ pub fn get_collider(
 }
 
 
+/// Bounding box in unity space, uses the part colliders when there's a registry to get them from
+pub fn bounding_box(parts: &[Part], part_registry: Option<&PartRegistry>) -> Option<(Vec3, Vec3)> {
+    let mut min = Vec3::INFINITY;
+    let mut max = Vec3::NEG_INFINITY;
+    for part in parts {
+        let (base_part, adjustable_hull, _) = part.to_optionals();
+        match part_registry.and_then(|registry| registry.parts.get(&base_part.id)) {
+            Some(part_data) => {
+                let collider = get_collider(base_part, adjustable_hull, part_data);
+                for corner in 0..8 {
+                    let local = Vec3::new(
+                        if corner & 1 == 0 { -0.5 } else { 0.5 },
+                        if corner & 2 == 0 { -0.5 } else { 0.5 },
+                        if corner & 4 == 0 { -0.5 } else { 0.5 },
+                    );
+                    let point = bevy_to_unity_translation(&collider.transform_point(local));
+                    min = min.min(point);
+                    max = max.max(point);
+                }
+            }
+            None => {
+                min = min.min(base_part.position);
+                max = max.max(base_part.position);
+            }
+        }
+    }
+    if parts.is_empty() { return None; }
+    return Some((min, max));
+}
+
 
 pub fn generate_adjustable_hull_mesh(mesh: &mut Mesh, adjustable_hull: &AdjustableHull) {
+    let (vertices, indices) = adjustable_hull_triangles(adjustable_hull);
+
+    //mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0,0.0,1.0]; vertices.len()]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0,0.0]; vertices.len()]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
+    mesh.insert_indices(Indices::U32(indices));
+
+    mesh.duplicate_vertices();
+    mesh.compute_flat_normals();
+
+}
+
+/// The closed triangle mesh of a hull, in the part's local space
+pub fn adjustable_hull_triangles(adjustable_hull: &AdjustableHull) -> (Vec<[f32; 3]>, Vec<u32>) {
     let resolution = 6*4;
 
     let mut vertices : Vec<[f32;3]> = Vec::with_capacity((resolution+1)*2);
@@ -146,30 +198,38 @@ pub fn generate_adjustable_hull_mesh(mesh: &mut Mesh, adjustable_hull: &Adjustab
     indices.extend_from_slice(&[(lengths-1)+(lengths)-1, index,         (lengths-1)-1]);
     indices.extend_from_slice(&[(lengths-1)+(lengths)-1, index+lengths, index]);
 
+    return (vertices, indices);
+}
 
+/// A point on the outline of a hull's front or back, `angle` going around from the right side,
+/// returns x and y in the part's local space
+pub fn adjustable_hull_section_point(adjustable_hull: &AdjustableHull, angle: f32, front: bool) -> [f32; 2] {
+    let half_width = (if front {adjustable_hull.front_width} else {adjustable_hull.back_width} )*0.5;
+    let half_spread = (if front {adjustable_hull.front_spread} else {adjustable_hull.back_spread})*0.5;
+    let height_multiplier = if front {adjustable_hull.height_scale*adjustable_hull.height*0.5} else {adjustable_hull.height*0.5};
+    let max_half_height = adjustable_hull.height*0.5;
+    let height_offset = if front {adjustable_hull.height_offset*adjustable_hull.height} else {0.0};
 
+    let cos_angle = f32::cos(angle);
+    let sin_angle = f32::sin(angle);
 
+    let multiplier: f32 = f32::lerp(
+        1.0 / f32::max(f32::abs(sin_angle),f32::abs(cos_angle)),
+        1.0,
+        if sin_angle>0.0 {adjustable_hull.top_roundness} else {adjustable_hull.bottom_roundness}
+    );
 
+    //println!("the height {} percentage is {} and the cosangle is {} but times multiplier is {} and the lerp is from {} to {} ", sin_angle*multiplier,((sin_angle*multiplier)/2.0)+0.5,cos_angle,cos_angle*multiplier,half_width,half_width+half_spread);
 
-
-
-    //mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0,0.0,1.0]; vertices.len()]);
-    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0,0.0]; vertices.len()]);
-    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
-    mesh.insert_indices(Indices::U32(indices));
-
-    mesh.duplicate_vertices();
-    mesh.compute_flat_normals();
-
+    //vertices.push([(cos_angle*multiplier*half_width)+(cos_angle.signum()*(((sin_angle/2.0)+1.0)*half_spread)), f32::clamp((sin_angle*multiplier*height_multiplier)+height_offset,-max_height,max_height), (if front {0.5} else {-0.5})*adjustable_hull.length]);
+    return [
+        cos_angle*multiplier*f32::lerp(half_width,half_width+half_spread,((sin_angle*multiplier)/2.0)+0.5),
+        f32::clamp((sin_angle*multiplier*height_multiplier)+height_offset,-max_half_height,max_half_height),
+    ];
 }
 
 pub fn adjustable_hull_side(adjustable_hull: &AdjustableHull, resolution: usize, front: bool) -> (Vec<[f32; 3]>, Vec<u32>) {
     let mut vertices = Vec::with_capacity(resolution+1);
-    let half_width = (if front {adjustable_hull.front_width} else {adjustable_hull.back_width} )*0.5;
-    let half_spread = (if front {adjustable_hull.front_spread} else {adjustable_hull.back_spread})*0.5;
-    let height_multiplier = if front {adjustable_hull.height_scale*adjustable_hull.height*0.5} else {adjustable_hull.height*0.5};
-    let max_half_height = adjustable_hull.height*0.5;
-    let height_offset = if front {adjustable_hull.height_offset*adjustable_hull.height} else {0.0};
 
     //println!("=======================================the adjustable_hull is {:?}",adjustable_hull);
     //println!("=======================================the frontness is {:?}",front);
@@ -181,25 +241,10 @@ pub fn adjustable_hull_side(adjustable_hull: &AdjustableHull, resolution: usize,
 
     for i in 0..resolution {
         let angle = f32::consts::TAU*((i as f32)/(resolution as f32));
-        let cos_angle = f32::cos(angle);
-        let sin_angle = f32::sin(angle);
-
-        //println!("calced the cos and sin of {} ({}), which was {} and {}",angle,(i as f32/resolution as f32),cos_angle,sin_angle);
-
-        let multiplier: f32 = f32::lerp(
-            1.0 / f32::max(f32::abs(sin_angle),f32::abs(cos_angle)),
-            1.0,
-            if sin_angle>0.0 {adjustable_hull.top_roundness} else {adjustable_hull.bottom_roundness}
-        );
-
-        //println!("the height {} percentage is {} and the cosangle is {} but times multiplier is {} and the lerp is from {} to {} ", sin_angle*multiplier,((sin_angle*multiplier)/2.0)+0.5,cos_angle,cos_angle*multiplier,half_width,half_width+half_spread);
-
-        
-
-        //vertices.push([(cos_angle*multiplier*half_width)+(cos_angle.signum()*(((sin_angle/2.0)+1.0)*half_spread)), f32::clamp((sin_angle*multiplier*height_multiplier)+height_offset,-max_height,max_height), (if front {0.5} else {-0.5})*adjustable_hull.length]);
+        let point = adjustable_hull_section_point(adjustable_hull, angle, front);
         vertices.push([
-            cos_angle*multiplier*f32::lerp(half_width,half_width+half_spread,((sin_angle*multiplier)/2.0)+0.5),
-            f32::clamp((sin_angle*multiplier*height_multiplier)+height_offset,-max_half_height,max_half_height),
+            point[0],
+            point[1],
             (if front {0.5} else {-0.5})*adjustable_hull.length
         ]);
         sum_x+=vertices.last().unwrap()[0];
@@ -297,9 +342,9 @@ pub fn place_part<'a>(
 
 
     match part {
-        Part::Normal(base_part) => entity.insert(*base_part),
-        Part::AdjustableHull(base_part, adjustable_hull) => entity.insert((*base_part, *adjustable_hull)),
-        Part::Turret(base_part, turret) => entity.insert((*base_part, *turret)),
+        Part::Normal(base_part) => entity.insert(base_part.clone()),
+        Part::AdjustableHull(base_part, adjustable_hull) => entity.insert((base_part.clone(), *adjustable_hull)),
+        Part::Turret(base_part, turret) => entity.insert((base_part.clone(), *turret)),
     };
 
     if let Part::AdjustableHull(base_part, adjustable_hull) = part {
@@ -521,7 +566,7 @@ impl PartAttributes {
         if let Some(base_part) = base_part {
             match self {
                 PartAttributes::Id => {base_part.id = text.parse()?},
-                PartAttributes::IgnorePhysics => {base_part.id = text.parse()?},
+                PartAttributes::IgnorePhysics => {base_part.ignore_physics = text.to_lowercase().parse()?},
                 PartAttributes::PositionX => {base_part.position.x = text.parse()?},
                 PartAttributes::PositionY => {base_part.position.y = text.parse()?},
                 PartAttributes::PositionZ => {base_part.position.z = text.parse()?},
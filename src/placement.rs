@@ -0,0 +1,136 @@
+//! Placing pasted and new parts on the surface under the cursor.
+//!
+//! Pasting or spawning starts a placement instead of spawning straight away. Every frame the
+//! parts are turned so their up points out of the face under the cursor, lined up with the part
+//! that face belongs to, and pushed out along the face until they rest flush on it.
+//! They're drawn as a ghost until a click places them, escape cancels.
+
+use bevy::{color::Color, input::{mouse::MouseButton, ButtonInput}, math::{Dir3, Quat, Vec3}, picking::mesh_picking::ray_cast::{MeshRayCast, RayCastSettings}, prelude::{Camera, Gizmos, GlobalTransform, KeyCode, Query, Res, ResMut, Single, Transform}, window::Window};
+use bevy_egui::EguiContexts;
+
+use crate::{cam_movement::EditorCamera, editor::EditorData, editor_actions::EditorActionEvent, editor_utils::{dir_from_index, round_to_axis}, parsing::{AdjustableHull, BasePart, Part}, parts::{bevy_quat_to_unity, bevy_to_unity_translation, get_collider, unity_to_bevy_quat, unity_to_bevy_translation, BasePartMesh, PartRegistry}};
+
+/// How far in front of the camera parts go when there's nothing under the cursor
+pub const EMPTY_PLACE_DISTANCE: f32 = 100.0;
+
+pub struct Placement {
+    //as they were copied
+    pub parts: Vec<Part>,
+    pub selected: bool,
+    //where they'd go if placed now
+    pub placed: Vec<Part>,
+    //turns floating on once placed, so a new part can be carried on to where it's wanted
+    pub floating: bool,
+}
+
+impl Placement {
+    pub fn new(parts: Vec<Part>, selected: bool) -> Placement {
+        return Placement { placed: parts.clone(), parts, selected, floating: false };
+    }
+}
+
+fn part_translation(part: &Part) -> Vec3 {
+    return unity_to_bevy_translation(&part.base_part().position);
+}
+
+fn collider_of(part: &Part, part_registry: &PartRegistry) -> Option<Transform> {
+    let (base_part, adjustable_hull, _) = part.to_optionals();
+    return Some(get_collider(base_part, adjustable_hull, part_registry.parts.get(&base_part.id)?));
+}
+
+/// Turns the parts by `rotation` around their middle and moves the middle to `point`
+pub fn transform_group(parts: &[Part], rotation: Quat, point: Vec3) -> Vec<Part> {
+    let pivot = parts.iter().map(part_translation).sum::<Vec3>() / (parts.len().max(1) as f32);
+
+    let mut moved = Vec::with_capacity(parts.len());
+    for part in parts {
+        let mut part = part.clone();
+        let translation = point + rotation.mul_vec3(part_translation(&part)-pivot);
+        let base_part = part.base_part_mut();
+        base_part.position = bevy_to_unity_translation(&translation);
+        base_part.rotation = bevy_quat_to_unity(&(rotation*unity_to_bevy_quat(&base_part.rotation)));
+        moved.push(part);
+    }
+    return moved;
+}
+
+/// The parts lined up with face `face` of `surface` and resting on it at `point`
+pub fn align_to_face(parts: &[Part], surface: &Transform, face: u8, point: Vec3, part_registry: &PartRegistry) -> Vec<Part> {
+    let normal = surface.rotation.mul_vec3(dir_from_index(&face));
+    let rotation = surface.rotation*Quat::from_rotation_arc(Vec3::Y, dir_from_index(&face));
+    let mut aligned = transform_group(parts, rotation, point);
+
+    //the lowest corner along the normal goes onto the face's plane
+    let mut lowest = f32::INFINITY;
+    for part in &aligned {
+        let Some(collider) = collider_of(part, part_registry) else {continue;};
+        for corner in 0..8 {
+            let sign = Vec3::new(
+                if corner & 1 == 0 {-0.5} else {0.5},
+                if corner & 2 == 0 {-0.5} else {0.5},
+                if corner & 4 == 0 {-0.5} else {0.5},
+            );
+            let corner_position = collider.translation + collider.rotation.mul_vec3(collider.scale*sign);
+            lowest = lowest.min((corner_position-point).dot(normal));
+        }
+    }
+    if lowest.is_finite() {
+        let offset = bevy_to_unity_translation(&(normal*-lowest));
+        for part in &mut aligned {
+            part.base_part_mut().position += offset;
+        }
+    }
+    return aligned;
+}
+
+pub fn update_placement(
+    mut editor_data: ResMut<EditorData>,
+    camera_query: Single<(&Camera, &GlobalTransform, &EditorCamera)>,
+    windows: Single<&Window>,
+    mut ray_cast: MeshRayCast,
+    base_part_mesh_query: Query<&BasePartMesh>,
+    part_query: Query<(&BasePart, Option<&AdjustableHull>)>,
+    part_registry: Res<PartRegistry>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    key: Res<ButtonInput<KeyCode>>,
+    mut contexts: EguiContexts,
+    mut gizmo: Gizmos,
+){
+    let Some(placement) = editor_data.placing.as_mut() else {return;};
+    if key.just_pressed(KeyCode::Escape) {
+        editor_data.placing = None;
+        return;
+    }
+
+    let (camera, camera_transform, _) = *camera_query;
+    let Some(cursor_position) = windows.cursor_position() else {return;};
+    let Ok(ray) = camera.viewport_to_world(camera_transform, cursor_position) else {return;};
+
+    let hit = ray_cast.cast_ray(ray, &RayCastSettings {
+        filter: &|entity| base_part_mesh_query.contains(entity),
+        ..RayCastSettings::default()
+    }).first().and_then(|(hit_entity, hit)| {
+        let base_entity = base_part_mesh_query.get(*hit_entity).ok()?.base_part;
+        let (base_part, adjustable_hull) = part_query.get(base_entity).ok()?;
+        let surface = get_collider(base_part, adjustable_hull, part_registry.parts.get(&base_part.id)?);
+        return Some((surface, hit.point, hit.normal));
+    });
+
+    if let Some((surface, point, normal)) = hit {
+        let face = round_to_axis(&surface, &Dir3::new(normal).unwrap_or(Dir3::Y));
+        placement.placed = align_to_face(&placement.parts, &surface, face, point, &part_registry);
+        gizmo.arrow(point, point+surface.rotation.mul_vec3(dir_from_index(&face)), Color::srgb_u8(255, 255, 0));
+    } else {
+        placement.placed = transform_group(&placement.parts, Quat::IDENTITY, ray.origin+(*ray.direction*EMPTY_PLACE_DISTANCE));
+    }
+
+    for part in &placement.placed {
+        let Some(collider) = collider_of(part, &part_registry) else {continue;};
+        gizmo.cuboid(collider, Color::srgba(0.6, 0.9, 1.0, 0.6));
+    }
+
+    let over_ui = contexts.ctx_mut().is_pointer_over_area();
+    if mouse.just_pressed(MouseButton::Left) && !over_ui {
+        editor_data.queued_actions.push_front(EditorActionEvent::Place {});
+    }
+}
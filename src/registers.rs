@@ -0,0 +1,68 @@
+//! Where yanked parts go, like vim's registers.
+//!
+//! `"ayy` yanks the selection into `a` and `"ap` pastes it, `"Ayy` adds to `a` instead.
+//! Every yank and delete also goes onto a ring of the last few, `p` pastes the newest and
+//! `"1p` the one before it. `"+` is the system clipboard, as NavalArt `<part>` xml.
+
+use std::collections::VecDeque;
+
+use bevy::utils::HashMap;
+use bevy_egui::EguiClipboard;
+
+use crate::parsing::{parse_parts, parts_to_xml, Part};
+
+/// How many yanks the ring remembers
+pub const YANK_RING_SIZE: usize = 10;
+pub const CLIPBOARD_REGISTER: char = '+';
+
+pub fn is_register(register: char) -> bool {
+    return register.is_ascii_alphanumeric() || register == CLIPBOARD_REGISTER;
+}
+
+/// Macros can go in any register but the clipboard, which only holds parts
+pub fn is_macro_register(register: char) -> bool {
+    return is_register(register) && register != CLIPBOARD_REGISTER;
+}
+
+#[derive(Default)]
+pub struct Registers {
+    named: HashMap<char, Vec<Part>>,
+    //newest first
+    ring: VecDeque<Vec<Part>>,
+}
+
+impl Registers {
+    pub fn yank(&mut self, register: Option<char>, parts: Vec<Part>, clipboard: &mut EguiClipboard) {
+        match register {
+            Some(CLIPBOARD_REGISTER) => clipboard.set_contents(&parts_to_xml(&parts)),
+            Some(register) if register.is_ascii_uppercase() => {
+                self.named.entry(register.to_ascii_lowercase()).or_default().extend(parts.iter().cloned());
+            },
+            Some(register) if register.is_ascii_lowercase() => {
+                self.named.insert(register, parts.clone());
+            },
+            _ => {},
+        }
+
+        self.ring.push_front(parts);
+        self.ring.truncate(YANK_RING_SIZE);
+    }
+
+    pub fn get(&self, register: Option<char>, clipboard: &mut EguiClipboard) -> Option<Vec<Part>> {
+        let Some(register) = register else { return self.ring.front().cloned(); };
+
+        if register == CLIPBOARD_REGISTER {
+            let text = clipboard.get_contents()?;
+            let (parts, problems) = parse_parts(&text);
+            for problem in &problems {
+                println!("problem in the clipboard's parts: {}",problem);
+            }
+            if parts.is_empty() { return None; }
+            return Some(parts);
+        }
+        if let Some(index) = register.to_digit(10) {
+            return self.ring.get(index as usize).cloned();
+        }
+        return self.named.get(&register.to_ascii_lowercase()).cloned();
+    }
+}
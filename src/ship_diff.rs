@@ -0,0 +1,188 @@
+use std::fmt::Display;
+
+use enum_collections::Enumerated;
+
+use crate::{parsing::Part, parts::PartAttributes};
+
+/// Parts further apart than this are only treated as the same part when they're at the same index
+pub const MATCH_DISTANCE: f32 = 5.0;
+const NUMBER_TOLERANCE: f32 = 0.0001;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    Moved,
+    Recoloured,
+    Reshaped,
+    Other,
+}
+
+impl ChangeKind {
+    pub fn of(attribute: PartAttributes) -> ChangeKind {
+        match attribute {
+            PartAttributes::PositionX | PartAttributes::PositionY | PartAttributes::PositionZ |
+            PartAttributes::RotationX | PartAttributes::RotationY | PartAttributes::RotationZ => ChangeKind::Moved,
+            PartAttributes::Color => ChangeKind::Recoloured,
+            PartAttributes::ScaleX | PartAttributes::ScaleY | PartAttributes::ScaleZ => ChangeKind::Reshaped,
+            attribute if attribute.is_adjustable_hull() => ChangeKind::Reshaped,
+            _ => ChangeKind::Other,
+        }
+    }
+}
+
+impl Display for ChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"{}",match self {
+            ChangeKind::Moved => "moved",
+            ChangeKind::Recoloured => "recoloured",
+            ChangeKind::Reshaped => "reshaped",
+            ChangeKind::Other => "changed",
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FieldChange {
+    pub attribute: PartAttributes,
+    //None when the part doesn't have the field, like a turret field on a normal part
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// An old part and the new part it became
+#[derive(Clone, Debug)]
+pub struct PartMatch {
+    pub old_index: usize,
+    pub new_index: usize,
+    pub changes: Vec<FieldChange>,
+}
+
+impl PartMatch {
+    pub fn kinds(&self) -> Vec<ChangeKind> {
+        let mut kinds: Vec<ChangeKind> = Vec::new();
+        for change in &self.changes {
+            let kind = ChangeKind::of(change.attribute);
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+        return kinds;
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ShipDiff {
+    //every matched pair, including the ones that didn't change
+    pub matches: Vec<PartMatch>,
+    //indices into the new parts
+    pub added: Vec<usize>,
+    //indices into the old parts
+    pub removed: Vec<usize>,
+}
+
+impl ShipDiff {
+    pub fn changed(&self) -> impl Iterator<Item = &PartMatch> {
+        return self.matches.iter().filter(|part_match| !part_match.changes.is_empty());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.added.is_empty() && self.removed.is_empty() && self.changed().next().is_none();
+    }
+}
+
+fn values_differ(attribute: PartAttributes, old: &Option<String>, new: &Option<String>) -> bool {
+    if let (Some(old), Some(new)) = (old, new) {
+        if attribute.is_number() {
+            if let (Ok(old), Ok(new)) = (old.parse::<f32>(), new.parse::<f32>()) {
+                return (old-new).abs() > NUMBER_TOLERANCE;
+            }
+        }
+    }
+    return old != new;
+}
+
+/// Every `PartAttributes` field that differs between two parts
+pub fn field_changes(old: &Part, new: &Part) -> Vec<FieldChange> {
+    let old_fields = old.to_optionals();
+    let new_fields = new.to_optionals();
+    let mut changes = Vec::new();
+    for attribute in PartAttributes::VARIANTS {
+        let old_value = attribute.get_field(old_fields.0, old_fields.1, old_fields.2);
+        let new_value = attribute.get_field(new_fields.0, new_fields.1, new_fields.2);
+        if values_differ(*attribute, &old_value, &new_value) {
+            changes.push(FieldChange { attribute: *attribute, old: old_value, new: new_value });
+        }
+    }
+    return changes;
+}
+
+/// Pairs up parts with the same id, unchanged ones first, then ones still at the same index however far
+/// they moved, then the closest and most similar, anything left over was added or removed
+pub fn diff_parts(old: &[Part], new: &[Part]) -> ShipDiff {
+    let mut candidates: Vec<(f32, usize, usize, Vec<FieldChange>)> = Vec::new();
+    for (new_index, new_part) in new.iter().enumerate() {
+        for (old_index, old_part) in old.iter().enumerate() {
+            if old_part.base_part().id != new_part.base_part().id { continue; }
+            let distance = old_part.base_part().position.distance(new_part.base_part().position);
+            if distance > MATCH_DISTANCE && old_index != new_index { continue; }
+            candidates.push((distance, old_index, new_index, field_changes(old_part, new_part)));
+        }
+    }
+    candidates.sort_by(|a, b| {
+        a.3.len().min(1).cmp(&b.3.len().min(1))
+            .then((a.1 != a.2).cmp(&(b.1 != b.2)))
+            .then(a.0.total_cmp(&b.0))
+            .then(a.3.len().cmp(&b.3.len()))
+            .then(a.1.cmp(&b.1))
+            .then(a.2.cmp(&b.2))
+    });
+
+    let mut old_matched = vec![false; old.len()];
+    let mut new_matched = vec![false; new.len()];
+    let mut diff = ShipDiff::default();
+    for (_, old_index, new_index, changes) in candidates {
+        if old_matched[old_index] || new_matched[new_index] { continue; }
+        old_matched[old_index] = true;
+        new_matched[new_index] = true;
+        diff.matches.push(PartMatch { old_index, new_index, changes });
+    }
+    diff.matches.sort_by_key(|part_match| part_match.new_index);
+    diff.added = (0..new.len()).filter(|index| !new_matched[*index]).collect();
+    diff.removed = (0..old.len()).filter(|index| !old_matched[*index]).collect();
+    return diff;
+}
+
+fn describe(index: usize, part: &Part) -> String {
+    let base_part = part.base_part();
+    return format!("#{} id {} at ({}, {}, {})",index,base_part.id,base_part.position.x,base_part.position.y,base_part.position.z);
+}
+
+/// Human readable report, one line per part with the changed fields indented under it
+pub fn diff_report(old: &[Part], new: &[Part], diff: &ShipDiff) -> String {
+    let mut report = String::new();
+    for index in &diff.removed {
+        report += &format!("- {}\n",describe(*index, &old[*index]));
+    }
+    for index in &diff.added {
+        report += &format!("+ {}\n",describe(*index, &new[*index]));
+    }
+    for part_match in diff.changed() {
+        let kinds: Vec<String> = part_match.kinds().iter().map(|kind| kind.to_string()).collect();
+        report += &format!("~ {} ({})\n",describe(part_match.new_index, &new[part_match.new_index]),kinds.join(", "));
+        for change in &part_match.changes {
+            report += &format!(
+                "    {}: {} -> {}\n",
+                change.attribute,
+                change.old.as_deref().unwrap_or("-"),
+                change.new.as_deref().unwrap_or("-")
+            );
+        }
+    }
+    report += &format!(
+        "{} added, {} removed, {} changed, {} unchanged\n",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed().count(),
+        diff.matches.len()-diff.changed().count()
+    );
+    return report;
+}
@@ -0,0 +1,238 @@
+use std::fmt::Display;
+
+use enum_collections::Enumerated;
+
+use crate::{parsing::{Part, Ship, ShipMetadata}, parts::PartAttributes, ship_diff::{diff_parts, field_changes, ShipDiff}};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Side {
+    Ours,
+    Theirs,
+}
+
+impl Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"{}",match self {
+            Side::Ours => "ours",
+            Side::Theirs => "theirs",
+        })
+    }
+}
+
+/// Something both sides changed differently, the merge keeps `kept`'s version
+#[derive(Clone, Debug)]
+pub enum MergeConflict {
+    Attribute {
+        base_index: usize,
+        part_id: i32,
+        attribute: PartAttributes,
+        ours: Option<String>,
+        theirs: Option<String>,
+        kept: Side,
+    },
+    //one side removed a part the other side changed, the changed part is kept
+    RemovedAndChanged {
+        base_index: usize,
+        part_id: i32,
+        removed_by: Side,
+    },
+    Metadata {
+        field: &'static str,
+        ours: String,
+        theirs: String,
+        kept: Side,
+    },
+}
+
+impl Display for MergeConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeConflict::Attribute { base_index, part_id, attribute, ours, theirs, kept } => write!(
+                f,"part #{} (id {}): {} is {} in ours but {} in theirs, kept {}",
+                base_index,part_id,attribute,ours.as_deref().unwrap_or("-"),theirs.as_deref().unwrap_or("-"),kept
+            ),
+            MergeConflict::RemovedAndChanged { base_index, part_id, removed_by } => write!(
+                f,"part #{} (id {}): removed in {} but changed in the other side, kept the changed part",
+                base_index,part_id,removed_by
+            ),
+            MergeConflict::Metadata { field, ours, theirs, kept } => write!(
+                f,"ship {}: {:?} in ours but {:?} in theirs, kept {}",
+                field,ours,theirs,kept
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MergeResult {
+    pub ship: Ship,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// What one side did to a base part
+enum PartFate {
+    Removed,
+    Kept(usize),
+}
+
+fn fates(diff: &ShipDiff, base_len: usize) -> Vec<PartFate> {
+    let mut fates: Vec<PartFate> = (0..base_len).map(|_| PartFate::Removed).collect();
+    for part_match in &diff.matches {
+        fates[part_match.old_index] = PartFate::Kept(part_match.new_index);
+    }
+    return fates;
+}
+
+fn part_variant(part: &Part) -> u8 {
+    match part {
+        Part::Normal(_) => 0,
+        Part::AdjustableHull(_,_) => 1,
+        Part::Turret(_,_) => 2,
+    }
+}
+
+/// Merges one part field by field, conflicting fields keep ours
+fn merge_part(base_index: usize, base: &Part, ours: &Part, theirs: &Part, conflicts: &mut Vec<MergeConflict>) -> Part {
+    //a side that turned the part into a different kind of part decides what kind it is
+    let mut merged = if part_variant(ours) != part_variant(base) {
+        ours.clone()
+    } else if part_variant(theirs) != part_variant(base) {
+        theirs.clone()
+    } else {
+        base.clone()
+    };
+
+    let ours_changes = field_changes(base, ours);
+    let theirs_changes = field_changes(base, theirs);
+    let ours_fields = ours.to_optionals();
+    let theirs_fields = theirs.to_optionals();
+
+    for attribute in PartAttributes::VARIANTS {
+        let ours_changed = ours_changes.iter().any(|change| change.attribute == *attribute);
+        let theirs_changed = theirs_changes.iter().any(|change| change.attribute == *attribute);
+        let value = match (ours_changed, theirs_changed) {
+            (false, false) => continue,
+            (true, false) => attribute.get_field(ours_fields.0, ours_fields.1, ours_fields.2),
+            (false, true) => attribute.get_field(theirs_fields.0, theirs_fields.1, theirs_fields.2),
+            (true, true) => {
+                let ours_value = attribute.get_field(ours_fields.0, ours_fields.1, ours_fields.2);
+                let theirs_value = attribute.get_field(theirs_fields.0, theirs_fields.1, theirs_fields.2);
+                let same = field_changes(ours, theirs).iter().all(|change| change.attribute != *attribute);
+                if !same {
+                    conflicts.push(MergeConflict::Attribute {
+                        base_index,
+                        part_id: base.base_part().id,
+                        attribute: *attribute,
+                        ours: ours_value.clone(),
+                        theirs: theirs_value,
+                        kept: Side::Ours,
+                    });
+                }
+                ours_value
+            }
+        };
+        let Some(value) = value else { continue; };
+
+        let (base_part, adjustable_hull, turret) = match &mut merged {
+            Part::Normal(base_part) => (base_part, None, None),
+            Part::AdjustableHull(base_part, adjustable_hull) => (base_part, Some(adjustable_hull), None),
+            Part::Turret(base_part, turret) => (base_part, None, Some(turret)),
+        };
+        if let Err(error) = attribute.set_field(Some(base_part), adjustable_hull, turret, &value) {
+            println!("couldn't merge {} of part #{}: {:?}",attribute,base_index,error);
+        }
+    }
+
+    //xml the editor doesn't model can't be merged field by field, take whichever side touched it
+    merged.base_part_mut().extras = if ours.base_part().extras != base.base_part().extras {
+        ours.base_part().extras.clone()
+    } else {
+        theirs.base_part().extras.clone()
+    };
+
+    return merged;
+}
+
+fn merge_string(field: &'static str, base: &str, ours: &str, theirs: &str, conflicts: &mut Vec<MergeConflict>) -> String {
+    if ours == theirs || theirs == base {
+        return ours.to_owned();
+    }
+    if ours == base {
+        return theirs.to_owned();
+    }
+    conflicts.push(MergeConflict::Metadata { field, ours: ours.to_owned(), theirs: theirs.to_owned(), kept: Side::Ours });
+    return ours.to_owned();
+}
+
+/// Three way merge of ships, taking every change that only one side made
+pub fn merge_ships(base: &Ship, ours: &Ship, theirs: &Ship) -> MergeResult {
+    let mut conflicts: Vec<MergeConflict> = Vec::new();
+
+    let ours_diff = diff_parts(&base.parts, &ours.parts);
+    let theirs_diff = diff_parts(&base.parts, &theirs.parts);
+    let ours_fates = fates(&ours_diff, base.parts.len());
+    let theirs_fates = fates(&theirs_diff, base.parts.len());
+
+    //parts are ordered like ours, with parts only theirs has at the end
+    let mut merged: Vec<(usize, Part)> = Vec::new();
+    for (base_index, base_part) in base.parts.iter().enumerate() {
+        match (&ours_fates[base_index], &theirs_fates[base_index]) {
+            (PartFate::Removed, PartFate::Removed) => {}
+            (PartFate::Kept(ours_index), PartFate::Removed) => {
+                let ours_part = &ours.parts[*ours_index];
+                if !field_changes(base_part, ours_part).is_empty() {
+                    conflicts.push(MergeConflict::RemovedAndChanged { base_index, part_id: base_part.base_part().id, removed_by: Side::Theirs });
+                    merged.push((*ours_index, ours_part.clone()));
+                }
+            }
+            (PartFate::Removed, PartFate::Kept(theirs_index)) => {
+                let theirs_part = &theirs.parts[*theirs_index];
+                if !field_changes(base_part, theirs_part).is_empty() {
+                    conflicts.push(MergeConflict::RemovedAndChanged { base_index, part_id: base_part.base_part().id, removed_by: Side::Ours });
+                    merged.push((ours.parts.len()+*theirs_index, theirs_part.clone()));
+                }
+            }
+            (PartFate::Kept(ours_index), PartFate::Kept(theirs_index)) => {
+                merged.push((
+                    *ours_index,
+                    merge_part(base_index, base_part, &ours.parts[*ours_index], &theirs.parts[*theirs_index], &mut conflicts)
+                ));
+            }
+        }
+    }
+
+    for ours_index in &ours_diff.added {
+        merged.push((*ours_index, ours.parts[*ours_index].clone()));
+    }
+    //both sides adding the same part shouldn't make two of it
+    let ours_added: Vec<Part> = ours_diff.added.iter().map(|index| ours.parts[*index].clone()).collect();
+    let theirs_added: Vec<Part> = theirs_diff.added.iter().map(|index| theirs.parts[*index].clone()).collect();
+    let added_diff = diff_parts(&ours_added, &theirs_added);
+    for (added_index, theirs_index) in theirs_diff.added.iter().enumerate() {
+        let duplicate = added_diff.matches.iter().any(|part_match| part_match.new_index == added_index && part_match.changes.is_empty());
+        if !duplicate {
+            merged.push((ours.parts.len()+*theirs_index, theirs.parts[*theirs_index].clone()));
+        }
+    }
+    merged.sort_by_key(|pair| pair.0);
+
+    //anything in the header besides the metadata goes with whichever side changed it
+    let mut header = ours.header.clone();
+    if ours.header.root_extras == base.header.root_extras && ours.header.ship_extras == base.header.ship_extras {
+        header.root_extras = theirs.header.root_extras.clone();
+        header.ship_extras = theirs.header.ship_extras.clone();
+    }
+    header.metadata = ShipMetadata {
+        name: merge_string("name", &base.header.metadata.name, &ours.header.metadata.name, &theirs.header.metadata.name, &mut conflicts),
+        game_version: merge_string("game version", &base.header.metadata.game_version, &ours.header.metadata.game_version, &theirs.header.metadata.game_version, &mut conflicts),
+        description: merge_string("description", &base.header.metadata.description, &ours.header.metadata.description, &theirs.header.metadata.description, &mut conflicts),
+    };
+
+    return MergeResult {
+        ship: Ship {
+            header,
+            parts: merged.into_iter().map(|pair| pair.1).collect(),
+        },
+        conflicts,
+    };
+}
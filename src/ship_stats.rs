@@ -0,0 +1,201 @@
+//! The "Ship Stats" window: mass, price, part counts, size and center of mass.
+//!
+//! Each part's numbers are worked out when it's added or changed and kept, the totals are kept
+//! as running sums, so an edit only costs the parts it touched. Mass is density × volume × scale,
+//! with the volume of an adjustable hull taken from its actual shape instead of the part's volume.
+
+use std::collections::BTreeMap;
+
+use bevy::{math::Vec3, prelude::{Changed, Entity, Or, Query, RemovedComponents, Res, ResMut, Resource}, utils::HashMap};
+use bevy_egui::{egui::{self, Vec2b}, EguiContexts};
+
+use crate::{parsing::{AdjustableHull, BasePart, Part, Turret}, hull_geometry::adjustable_hull_geometry, parts::{base_part_to_bevy_transform, bevy_to_unity_translation, bounding_box, get_collider, PartData, PartRegistry}};
+
+#[derive(Debug, Copy, Clone)]
+pub struct PartStats {
+    pub mass: f32,
+    pub price: i32,
+    //unity space
+    pub center: Vec3,
+    pub min: Vec3,
+    pub max: Vec3,
+    pub builder_class: i32,
+    pub weapon_type: i32,
+}
+
+/// Volume and center of a part in unity space
+pub fn part_volume(part: &Part, part_data: &PartData) -> (f32, Vec3) {
+    let (base_part, adjustable_hull, _) = part.to_optionals();
+    let scale = base_part.scale.abs();
+    let Some(adjustable_hull) = adjustable_hull else {
+        let center = get_collider(base_part, None, part_data).translation;
+        return (part_data.volume*scale.x*scale.y*scale.z, bevy_to_unity_translation(&center));
+    };
+
+    let geometry = adjustable_hull_geometry(adjustable_hull);
+    let center = base_part_to_bevy_transform(base_part).transform_point(geometry.centroid);
+    return (geometry.volume*scale.x*scale.y*scale.z, bevy_to_unity_translation(&center));
+}
+
+pub fn part_stats(part: &Part, part_registry: &PartRegistry) -> Option<PartStats> {
+    let part_data = part_registry.parts.get(&part.base_part().id)?;
+    let (volume, center) = part_volume(part, part_data);
+    let (min, max) = bounding_box(std::slice::from_ref(part), Some(part_registry))?;
+    return Some(PartStats {
+        mass: part_data.density*volume,
+        price: part_data.price,
+        center,
+        min,
+        max,
+        builder_class: part_data.builder_class,
+        weapon_type: part_data.weapon_type,
+    });
+}
+
+#[derive(Resource, Default)]
+pub struct ShipStats {
+    parts: HashMap<Entity, PartStats>,
+    pub mass: f32,
+    pub price: i64,
+    //sum of mass × center, for the center of mass
+    moment: Vec3,
+    pub by_builder_class: BTreeMap<i32, usize>,
+    pub by_weapon_type: BTreeMap<i32, usize>,
+    //only worked out again when a part that could have been on the edge changes
+    bounds: Option<(Vec3, Vec3)>,
+    bounds_dirty: bool,
+}
+
+impl ShipStats {
+    pub fn insert(&mut self, entity: Entity, stats: PartStats) {
+        self.remove(entity);
+        self.mass += stats.mass;
+        self.price += stats.price as i64;
+        self.moment += stats.center*stats.mass;
+        *self.by_builder_class.entry(stats.builder_class).or_default() += 1;
+        *self.by_weapon_type.entry(stats.weapon_type).or_default() += 1;
+        //growing the box never needs the other parts
+        if let Some((min, max)) = self.bounds.as_mut() {
+            *min = min.min(stats.min);
+            *max = max.max(stats.max);
+        } else if self.parts.is_empty() {
+            self.bounds = Some((stats.min, stats.max));
+        }
+        self.parts.insert(entity, stats);
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        let Some(stats) = self.parts.remove(&entity) else {return;};
+        self.mass -= stats.mass;
+        self.price -= stats.price as i64;
+        self.moment -= stats.center*stats.mass;
+        for (counts, key) in [(&mut self.by_builder_class, stats.builder_class), (&mut self.by_weapon_type, stats.weapon_type)] {
+            if let Some(count) = counts.get_mut(&key) {
+                *count -= 1;
+                if *count == 0 { counts.remove(&key); }
+            }
+        }
+        self.bounds_dirty = true;
+        if self.parts.is_empty() {
+            //keeps the running sums from drifting away from zero
+            *self = ShipStats::default();
+        }
+    }
+
+    pub fn parts(&self) -> impl Iterator<Item = (&Entity, &PartStats)> {
+        return self.parts.iter();
+    }
+
+    pub fn part_count(&self) -> usize {
+        return self.parts.len();
+    }
+
+    pub fn center_of_mass(&self) -> Option<Vec3> {
+        if self.mass <= 0.0 { return None; }
+        return Some(self.moment/self.mass);
+    }
+
+    /// (min, max) in unity space
+    pub fn bounds(&mut self) -> Option<(Vec3, Vec3)> {
+        if self.bounds_dirty {
+            self.bounds_dirty = false;
+            self.bounds = None;
+            for stats in self.parts.values() {
+                self.bounds = Some(match self.bounds {
+                    Some((min, max)) => (min.min(stats.min), max.max(stats.max)),
+                    None => (stats.min, stats.max),
+                });
+            }
+        }
+        return self.bounds;
+    }
+}
+
+pub fn update_ship_stats(
+    mut ship_stats: ResMut<ShipStats>,
+    changed_parts: Query<(Entity, &BasePart, Option<&AdjustableHull>, Option<&Turret>), Or<(Changed<BasePart>, Changed<AdjustableHull>, Changed<Turret>)>>,
+    mut removed_parts: RemovedComponents<BasePart>,
+    part_registry: Res<PartRegistry>,
+){
+    for entity in removed_parts.read() {
+        ship_stats.remove(entity);
+    }
+    for (entity, base_part, adjustable_hull, turret) in &changed_parts {
+        match part_stats(&Part::from_optionals((base_part, adjustable_hull, turret)), &part_registry) {
+            Some(stats) => ship_stats.insert(entity, stats),
+            None => ship_stats.remove(entity),
+        }
+    }
+}
+
+pub fn ship_stats_window(
+    mut contexts: EguiContexts,
+    mut ship_stats: ResMut<ShipStats>,
+){
+    let ship_stats = ship_stats.as_mut();
+    let bounds = ship_stats.bounds();
+
+    egui::Window::new("Ship Stats|船只数据")
+        .resizable(Vec2b::new(false,false))
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("ship_stats").num_columns(2).show(ui, |ui| {
+                ui.label("Parts|零件");
+                ui.label(format!("{}",ship_stats.part_count()));
+                ui.end_row();
+                ui.label("Mass|质量");
+                ui.label(format!("{:.1}",ship_stats.mass));
+                ui.end_row();
+                ui.label("Price|价格");
+                ui.label(format!("{}",ship_stats.price));
+                ui.end_row();
+                if let Some((min, max)) = bounds {
+                    let size = max-min;
+                    ui.label("Length|长度");
+                    ui.label(format!("{:.2}",size.z));
+                    ui.end_row();
+                    ui.label("Beam|宽度");
+                    ui.label(format!("{:.2}",size.x));
+                    ui.end_row();
+                    ui.label("Height|高度");
+                    ui.label(format!("{:.2}",size.y));
+                    ui.end_row();
+                }
+                if let Some(center) = ship_stats.center_of_mass() {
+                    ui.label("Center of Mass|重心");
+                    ui.label(format!("{:.2} {:.2} {:.2}",center.x,center.y,center.z));
+                    ui.end_row();
+                }
+            });
+
+            ui.collapsing("By Builder Class|按建造类别", |ui| {
+                for (builder_class, count) in &ship_stats.by_builder_class {
+                    ui.label(format!("{}: {}",builder_class,count));
+                }
+            });
+            ui.collapsing("By Weapon Type|按武器类型", |ui| {
+                for (weapon_type, count) in &ship_stats.by_weapon_type {
+                    ui.label(format!("{}: {}",weapon_type,count));
+                }
+            });
+        });
+}
@@ -0,0 +1,223 @@
+//! The righting arm (GZ) curve, the "Stability" window.
+//!
+//! The ship is heeled toward unity +x in steps from upright to 90°, keeping its displacement and
+//! trim, by tilting the water instead of the ship. At each angle the waterline is found again so
+//! the same volume is under water, and GZ is how far the center of buoyancy sits from the center
+//! of mass across the heel. While it's positive the ship rights itself, where it crosses zero is
+//! the angle of vanishing stability and past that it capsizes.
+//!
+//! A sweep clips every part some forty times per angle so it's only worked out on request.
+
+use std::path::Path;
+
+use bevy::{math::Vec3, prelude::{Changed, Or, Query, RemovedComponents, Res, ResMut, Resource}};
+use bevy_egui::{egui::{self, Color32, Stroke, Vec2b}, EguiContexts};
+
+use crate::{hydrostatics::{part_meshes, submerged, waterline_for_volume, Hydrostatics, Triangle}, parsing::{AdjustableHull, BasePart, Turret}, parts::{unity_to_bevy_translation, PartRegistry}, ship_stats::ShipStats};
+
+pub const HEEL_STEP_DEGREES: f32 = 2.0;
+pub const MAX_HEEL_DEGREES: f32 = 90.0;
+
+#[derive(Debug, Copy, Clone)]
+pub struct GzPoint {
+    pub heel: f32,
+    pub gz: f32,
+    //along the tilted water's normal
+    pub waterline: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct GzCurve {
+    pub displacement: f32,
+    pub points: Vec<GzPoint>,
+    //(heel, gz)
+    pub max_gz: (f32, f32),
+    //None if it's still righting at the last angle
+    pub vanishing_stability: Option<f32>,
+}
+
+/// Water normal in bevy space for a heel toward unity +x, which is bevy -x going down
+fn heeled_normal(heel_degrees: f32) -> Vec3 {
+    let heel = heel_degrees.to_radians();
+    return Vec3::new(heel.sin(), heel.cos(), 0.0);
+}
+
+/// GZ of the meshes floating `volume` at a heel, with the center of mass in bevy space
+pub fn righting_arm(meshes: &[Vec<Triangle>], volume: f32, center_of_mass: Vec3, heel_degrees: f32) -> Option<GzPoint> {
+    let normal = heeled_normal(heel_degrees);
+    let waterline = waterline_for_volume(meshes, normal, volume)?;
+    let buoyancy = submerged(meshes, normal*waterline, normal).centroid;
+    //the buoyancy pushes along the normal and the weight the other way, GZ is the lever between them
+    let heel = heel_degrees.to_radians();
+    let gz = (buoyancy-center_of_mass).dot(Vec3::new(-heel.cos(), heel.sin(), 0.0));
+    return Some(GzPoint { heel: heel_degrees, gz, waterline });
+}
+
+pub fn gz_curve(meshes: &[Vec<Triangle>], volume: f32, water_density: f32, center_of_mass: Vec3) -> Result<GzCurve, String> {
+    let steps = (MAX_HEEL_DEGREES/HEEL_STEP_DEGREES).round() as usize;
+    let mut points = Vec::with_capacity(steps+1);
+    for step in 0..=steps {
+        let heel = step as f32*HEEL_STEP_DEGREES;
+        let Some(point) = righting_arm(meshes, volume, center_of_mass, heel) else {
+            return Err("the ship sinks, it weighs more than the water all of it can push away".to_owned());
+        };
+        points.push(point);
+    }
+
+    let mut max_gz = (0.0, 0.0);
+    for point in &points {
+        if point.gz > max_gz.1 {
+            max_gz = (point.heel, point.gz);
+        }
+    }
+    //the first time it goes from righting to capsizing, in between the two angles
+    let mut vanishing_stability = None;
+    for pair in points.windows(2) {
+        if pair[0].gz > 0.0 && pair[1].gz <= 0.0 {
+            let along = pair[0].gz/(pair[0].gz-pair[1].gz);
+            vanishing_stability = Some(pair[0].heel + along*(pair[1].heel-pair[0].heel));
+            break;
+        }
+    }
+    //never righting at all is capsizing right away
+    if max_gz.1 <= 0.0 {
+        vanishing_stability = Some(0.0);
+    }
+    return Ok(GzCurve { displacement: volume*water_density, points, max_gz, vanishing_stability });
+}
+
+pub fn write_gz_csv(path: &Path, curve: &GzCurve) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["heel", "gz", "waterline"])?;
+    for point in &curve.points {
+        writer.write_record([point.heel.to_string(), point.gz.to_string(), point.waterline.to_string()])?;
+    }
+    writer.flush()?;
+    return Ok(());
+}
+
+#[derive(Resource)]
+pub struct Stability {
+    pub curve: Option<Result<GzCurve, String>>,
+    //the parts changed since the curve was worked out
+    pub stale: bool,
+    pub requested: bool,
+    pub csv_path: String,
+}
+
+impl Default for Stability {
+    fn default() -> Self {
+        return Stability { curve: None, stale: false, requested: false, csv_path: "gz_curve.csv".to_owned() };
+    }
+}
+
+pub fn update_stability(
+    mut stability: ResMut<Stability>,
+    hydrostatics: Res<Hydrostatics>,
+    ship_stats: Res<ShipStats>,
+    changed_parts: Query<(), Or<(Changed<BasePart>, Changed<AdjustableHull>, Changed<Turret>)>>,
+    mut removed_parts: RemovedComponents<BasePart>,
+    parts: Query<(&BasePart, Option<&AdjustableHull>)>,
+    part_registry: Res<PartRegistry>,
+){
+    if !changed_parts.is_empty() || removed_parts.read().count() > 0 {
+        stability.stale = true;
+    }
+    if !stability.requested { return; }
+    stability.requested = false;
+    stability.stale = false;
+
+    let Some(center_of_mass) = ship_stats.center_of_mass() else {
+        stability.curve = Some(Err("the ship has no mass".to_owned()));
+        return;
+    };
+    let meshes = part_meshes(parts.iter(), &part_registry);
+    let volume = ship_stats.mass/hydrostatics.water_density;
+    stability.curve = Some(gz_curve(&meshes, volume, hydrostatics.water_density, unity_to_bevy_translation(&center_of_mass)));
+}
+
+fn gz_plot(ui: &mut egui::Ui, curve: &GzCurve) {
+    let (response, painter) = ui.allocate_painter(egui::vec2(320.0, 160.0), egui::Sense::hover());
+    let rect = response.rect.shrink(4.0);
+    let text_color = ui.visuals().text_color();
+    let axis_stroke = Stroke::new(1.0, ui.visuals().weak_text_color());
+
+    let highest = curve.points.iter().map(|point| point.gz).fold(0.0, f32::max).max(0.01);
+    let lowest = curve.points.iter().map(|point| point.gz).fold(0.0, f32::min).min(-0.1*highest);
+    let to_screen = |heel: f32, gz: f32| egui::pos2(
+        rect.left() + rect.width()*heel/MAX_HEEL_DEGREES,
+        rect.bottom() - rect.height()*(gz-lowest)/(highest-lowest),
+    );
+
+    painter.line_segment([to_screen(0.0, 0.0), to_screen(MAX_HEEL_DEGREES, 0.0)], axis_stroke);
+    painter.line_segment([rect.left_top(), rect.left_bottom()], axis_stroke);
+    for heel in [30.0, 60.0, 90.0] {
+        let at = to_screen(heel, 0.0);
+        painter.line_segment([at, at+egui::vec2(0.0, 4.0)], axis_stroke);
+        painter.text(at+egui::vec2(0.0, 5.0), egui::Align2::RIGHT_TOP, format!("{}°",heel), egui::FontId::monospace(10.0), text_color);
+    }
+    painter.text(rect.left_top()+egui::vec2(3.0, 0.0), egui::Align2::LEFT_TOP, format!("{:.2}",highest), egui::FontId::monospace(10.0), text_color);
+
+    let line: Vec<egui::Pos2> = curve.points.iter().map(|point| to_screen(point.heel, point.gz)).collect();
+    painter.add(egui::Shape::line(line, Stroke::new(2.0, Color32::from_rgb(0, 200, 255))));
+    if let Some(heel) = curve.vanishing_stability {
+        painter.circle_filled(to_screen(heel, 0.0), 3.0, Color32::from_rgb(255, 80, 80));
+    }
+
+    if let Some(hovered) = response.hover_pos() {
+        let heel = ((hovered.x-rect.left())/rect.width()*MAX_HEEL_DEGREES).clamp(0.0, MAX_HEEL_DEGREES);
+        let nearest = curve.points.iter().min_by(|a, b| (a.heel-heel).abs().total_cmp(&(b.heel-heel).abs()));
+        if let Some(nearest) = nearest {
+            response.on_hover_text(format!("{}°: {:.3}",nearest.heel,nearest.gz));
+        }
+    }
+}
+
+pub fn stability_window(
+    mut contexts: EguiContexts,
+    mut stability: ResMut<Stability>,
+){
+    let stability = stability.as_mut();
+
+    egui::Window::new("Stability|稳性")
+        .resizable(Vec2b::new(false,false))
+        .show(contexts.ctx_mut(), |ui| {
+            if ui.button("Work Out GZ Curve|计算复原力臂曲线").clicked() {
+                stability.requested = true;
+            }
+            let Some(curve) = &stability.curve else {return;};
+            if stability.stale {
+                ui.label("Out of date, the ship changed|已过期，船只已修改");
+            }
+            let curve = match curve {
+                Ok(curve) => curve,
+                Err(problem) => {
+                    ui.label(problem);
+                    return;
+                },
+            };
+
+            egui::Grid::new("stability").num_columns(2).show(ui, |ui| {
+                ui.label("Displacement|排水量");
+                ui.label(format!("{:.1}",curve.displacement));
+                ui.end_row();
+                ui.label("Max GZ|最大复原力臂");
+                ui.label(format!("{:.3} at {}°",curve.max_gz.1,curve.max_gz.0));
+                ui.end_row();
+                ui.label("Vanishing Stability|稳性消失角");
+                ui.label(curve.vanishing_stability.map_or(format!("> {}°",MAX_HEEL_DEGREES), |heel| format!("{:.1}°",heel)));
+                ui.end_row();
+            });
+            gz_plot(ui, curve);
+
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut stability.csv_path);
+                if ui.button("Export CSV|导出CSV").clicked() {
+                    match write_gz_csv(Path::new(&stability.csv_path), curve) {
+                        Ok(()) => println!("wrote the gz curve to {:?}", stability.csv_path),
+                        Err(error) => println!("failed to write the gz curve to {:?}: {:?}", stability.csv_path, error),
+                    }
+                }
+            });
+        });
+}
@@ -0,0 +1,132 @@
+//! Live symmetry: with `symmetry` on, parts are linked to the part mirrored across the centreline
+//! and whatever changes one side, moves, rotations, attributes or the edges of nearby hulls
+//! following along, is mirrored onto the other.
+//!
+//! Links are found by looking for a part with the same id at the mirrored position, for the whole
+//! ship when symmetry is turned on and for new parts as they appear. Editing both sides of a pair at
+//! once leaves both alone. Edits made by actions are mirrored before the undo snapshot is taken, so
+//! undoing one puts both sides back.
+
+use bevy::{prelude::{Added, Changed, Commands, Component, Entity, Local, Or, Query, Res, World}, utils::HashSet};
+
+use crate::{editor::{EditorOptions, PartChange}, editor_actions::set_part, mirror::{mirror_part, roughly_equal, MirrorPlane}, parsing::{AdjustableHull, BasePart, Part, Turret}};
+
+/// How close, in unity units, a part has to be to the mirrored position to be linked
+pub const SYMMETRY_TOLERANCE: f32 = 0.01;
+
+#[derive(Component, Debug, Copy, Clone)]
+pub struct MirrorLink {
+    pub counterpart: Entity,
+}
+
+pub fn link_mirrored_parts(
+    editor_options: Res<EditorOptions>,
+    mut was_on: Local<bool>,
+    added: Query<Entity, Added<BasePart>>,
+    parts: Query<(Entity, &BasePart, Option<&MirrorLink>)>,
+    mut commands: Commands,
+){
+    if !editor_options.symmetry {
+        *was_on = false;
+        return;
+    }
+    let relink_all = !*was_on;
+    *was_on = true;
+    if !relink_all && added.is_empty() {
+        return;
+    }
+
+    let plane = MirrorPlane::default();
+    let unlinked: Vec<(Entity, &BasePart)> = parts.iter()
+        .filter(|(_, _, link)| relink_all || link.is_none())
+        .map(|(entity, base_part, _)| (entity, base_part))
+        .collect();
+    let to_link: Vec<Entity> = if relink_all { unlinked.iter().map(|(entity, _)| *entity).collect() } else { added.iter().collect() };
+
+    let mut taken: HashSet<Entity> = HashSet::new();
+    for entity in to_link {
+        if taken.contains(&entity) { continue; }
+        let Ok((_, base_part, _)) = parts.get(entity) else {continue;};
+
+        let mirrored_position = plane.mirror_position(&base_part.position);
+        let counterpart = unlinked.iter().find(|(other, other_part)| {
+            *other != entity && !taken.contains(other) && other_part.id == base_part.id &&
+            other_part.position.distance(mirrored_position) < SYMMETRY_TOLERANCE
+        });
+        //parts on the centreline are their own mirror
+        let on_plane = plane.distance(&base_part.position).abs() < SYMMETRY_TOLERANCE;
+
+        match counterpart {
+            Some((counterpart, _)) if !on_plane => {
+                commands.entity(entity).insert(MirrorLink { counterpart: *counterpart });
+                commands.entity(*counterpart).insert(MirrorLink { counterpart: entity });
+                taken.insert(entity);
+                taken.insert(*counterpart);
+            },
+            _ => if relink_all {
+                commands.entity(entity).remove::<MirrorLink>();
+            },
+        }
+    }
+    if relink_all {
+        println!("symmetry linked {} pairs of parts", taken.len()/2);
+    }
+}
+
+pub fn propagate_symmetry(
+    editor_options: Res<EditorOptions>,
+    changed: Query<(Entity, &MirrorLink), Or<(Changed<BasePart>, Changed<AdjustableHull>, Changed<Turret>)>>,
+    parts: Query<(&BasePart, Option<&AdjustableHull>, Option<&Turret>)>,
+    mut commands: Commands,
+){
+    if !editor_options.symmetry {
+        return;
+    }
+
+    let changed_entities: HashSet<Entity> = changed.iter().map(|(entity, _)| entity).collect();
+    for (entity, link) in &changed {
+        if changed_entities.contains(&link.counterpart) { continue; }
+        let Ok(counterpart) = parts.get(link.counterpart) else {
+            commands.entity(entity).remove::<MirrorLink>();
+            continue;
+        };
+        let Ok(part) = parts.get(entity) else {continue;};
+
+        let counterpart = Part::from_optionals(counterpart);
+        let mut mirrored = mirror_part(&Part::from_optionals(part), &MirrorPlane::default());
+        mirrored.base_part_mut().extras = counterpart.base_part().extras.clone();
+        //the counterpart changing next frame comes back here, this is what stops it bouncing
+        if roughly_equal(&mirrored, &counterpart) { continue; }
+        set_part(&mut commands.entity(link.counterpart), &mirrored);
+    }
+}
+
+/// Mirrors what an action changed onto the counterparts right away, so the undo snapshot taken
+/// after it holds both sides
+pub fn mirror_changes(world: &mut World, changes: &[PartChange]) {
+    if !world.resource::<EditorOptions>().symmetry {
+        return;
+    }
+
+    let changed_entities: HashSet<Entity> = changes.iter().map(|change| change.entity).collect();
+    let mut query = world.query::<(Option<&MirrorLink>, &BasePart, Option<&AdjustableHull>, Option<&Turret>)>();
+    let mut mirrored_parts = Vec::new();
+    for change in changes {
+        let Some(part) = &change.after else {continue;};
+        let Ok((Some(link), _, _, _)) = query.get(world, change.entity) else {continue;};
+        if changed_entities.contains(&link.counterpart) { continue; }
+        let Ok((_, base_part, adjustable_hull, turret)) = query.get(world, link.counterpart) else {continue;};
+
+        let counterpart = Part::from_optionals((base_part, adjustable_hull, turret));
+        let mut mirrored = mirror_part(part, &MirrorPlane::default());
+        mirrored.base_part_mut().extras = counterpart.base_part().extras.clone();
+        if roughly_equal(&mirrored, &counterpart) { continue; }
+        mirrored_parts.push((link.counterpart, mirrored));
+    }
+
+    let mut commands = world.commands();
+    for (counterpart, mirrored) in mirrored_parts {
+        set_part(&mut commands.entity(counterpart), &mirrored);
+    }
+    world.flush();
+}
@@ -0,0 +1,301 @@
+//! Where the loaded ship settles, the "Trim & Heel" window.
+//!
+//! The ship floats level only if its center of buoyancy is straight under its center of mass.
+//! The water is tilted in both heel and trim until that holds, by Newton's method on how far
+//! off the vertical through the center of mass the buoyancy sits, keeping the displacement.
+//!
+//! The window also bins each part's mass along the length and lists the parts pulling the ship
+//! off level the most: the ones whose moment about the level center of buoyancy goes the same way
+//! as the whole ship's. Each part's mass sits at its center, so a long hull lands in one bin.
+
+use bevy::{color::Color, math::{Mat2, Vec2, Vec3}, prelude::{Changed, Entity, Gizmos, Or, Query, RemovedComponents, Res, ResMut, Resource}};
+use bevy_egui::{egui::{self, Color32, Stroke, Vec2b}, EguiContexts};
+
+use crate::{editor::EditorData, hydrostatics::{part_meshes, submerged, waterline_for_volume, Hydrostatics, Triangle}, parsing::{AdjustableHull, BasePart, Turret}, parts::{bevy_to_unity_translation, get_collider, unity_to_bevy_translation, PartRegistry}, ship_stats::{PartStats, ShipStats}};
+
+const NEWTON_STEPS: usize = 12;
+//degrees
+const NEWTON_DELTA: f32 = 0.25;
+const MAX_NEWTON_STEP: f32 = 10.0;
+const SETTLED: f32 = 0.01;
+pub const DISTRIBUTION_BINS: usize = 24;
+pub const CONTRIBUTOR_COUNT: usize = 5;
+
+/// Water normal in bevy space, heel toward unity +x and trim toward unity +z going down
+pub fn water_normal(heel_degrees: f32, trim_degrees: f32) -> Vec3 {
+    let (heel, trim) = (heel_degrees.to_radians(), trim_degrees.to_radians());
+    return Vec3::new(heel.sin()*trim.cos(), heel.cos()*trim.cos(), -trim.sin());
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Equilibrium {
+    pub heel: f32,
+    pub trim: f32,
+    //whether tipping it a little either way brings it back
+    pub stable: bool,
+}
+
+/// How far the buoyancy is off the vertical through the center of mass, across and along the ship
+fn levers(meshes: &[Vec<Triangle>], volume: f32, center_of_mass: Vec3, angles: Vec2) -> Option<Vec2> {
+    let normal = water_normal(angles.x, angles.y);
+    let waterline = waterline_for_volume(meshes, normal, volume)?;
+    let buoyancy = submerged(meshes, normal*waterline, normal).centroid;
+    let along = Vec3::X.cross(normal).normalize();
+    let across = normal.cross(along);
+    let offset = buoyancy-center_of_mass;
+    return Some(Vec2::new(offset.dot(across), offset.dot(along)));
+}
+
+pub fn equilibrium(meshes: &[Vec<Triangle>], volume: f32, center_of_mass: Vec3) -> Result<Equilibrium, String> {
+    let sinks = "the ship sinks, it weighs more than the water all of it can push away".to_owned();
+    let mut angles = Vec2::ZERO;
+    let mut jacobian = Mat2::IDENTITY;
+    let mut settled = false;
+    for _ in 0..NEWTON_STEPS {
+        let lever = levers(meshes, volume, center_of_mass, angles).ok_or(sinks.clone())?;
+        let heeled = levers(meshes, volume, center_of_mass, angles+Vec2::new(NEWTON_DELTA, 0.0)).ok_or(sinks.clone())?;
+        let trimmed = levers(meshes, volume, center_of_mass, angles+Vec2::new(0.0, NEWTON_DELTA)).ok_or(sinks.clone())?;
+        jacobian = Mat2::from_cols((heeled-lever)/NEWTON_DELTA, (trimmed-lever)/NEWTON_DELTA);
+        if jacobian.determinant().abs() < f32::EPSILON {
+            return Err("couldn't find where the ship settles".to_owned());
+        }
+        let step = (-(jacobian.inverse()*lever)).clamp(Vec2::splat(-MAX_NEWTON_STEP), Vec2::splat(MAX_NEWTON_STEP));
+        angles += step;
+        if step.abs().max_element() < SETTLED {
+            settled = true;
+            break;
+        }
+    }
+    if angles.abs().max_element() >= 90.0 {
+        return Err("the ship capsizes".to_owned());
+    }
+    if !settled {
+        return Err("couldn't find where the ship settles".to_owned());
+    }
+
+    return Ok(Equilibrium {
+        heel: angles.x,
+        trim: angles.y,
+        //tipping it further has to move the buoyancy toward the side going down, in both directions,
+        //heel takes -x down and trim takes +z down
+        stable: jacobian.x_axis.x < 0.0 && jacobian.y_axis.y > 0.0,
+    });
+}
+
+/// Mass in each of `bins` slices along unity z between `min` and `max`
+pub fn weight_distribution<'a>(parts: impl Iterator<Item = &'a PartStats>, min: f32, max: f32, bins: usize) -> Vec<f32> {
+    let mut distribution = vec![0.0; bins];
+    let width = (max-min).max(f32::EPSILON)/(bins as f32);
+    for stats in parts {
+        let bin = ((stats.center.z-min)/width).floor().clamp(0.0, (bins-1) as f32) as usize;
+        distribution[bin] += stats.mass;
+    }
+    return distribution;
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Contributor {
+    pub entity: Entity,
+    pub mass: f32,
+    pub moment: f32,
+}
+
+/// The parts with the biggest moments about `reference` along unity `axis` that go the same way as `net`
+pub fn imbalance_contributors<'a>(parts: impl Iterator<Item = (&'a Entity, &'a PartStats)>, reference: Vec3, axis: usize, net: f32) -> Vec<Contributor> {
+    let mut contributors: Vec<Contributor> = parts.map(|(entity, stats)| Contributor {
+        entity: *entity,
+        mass: stats.mass,
+        moment: stats.mass*(stats.center[axis]-reference[axis]),
+    }).filter(|contributor| contributor.moment*net > 0.0).collect();
+    contributors.sort_by(|a, b| b.moment.abs().total_cmp(&a.moment.abs()));
+    contributors.truncate(CONTRIBUTOR_COUNT);
+    return contributors;
+}
+
+#[derive(Debug, Clone)]
+pub struct TrimResult {
+    pub equilibrium: Equilibrium,
+    //how much lower one end is than the other over the waterline length
+    pub trim_drop: f32,
+    //unity space
+    pub center_of_mass: Vec3,
+    pub level_buoyancy: Vec3,
+    pub distribution: Vec<f32>,
+    pub distribution_range: (f32, f32),
+    pub heel_contributors: Vec<Contributor>,
+    pub trim_contributors: Vec<Contributor>,
+}
+
+#[derive(Resource, Default)]
+pub struct TrimPrediction {
+    pub result: Option<Result<TrimResult, String>>,
+    pub stale: bool,
+    pub requested: bool,
+    pub highlight: bool,
+}
+
+pub fn update_trim_prediction(
+    mut trim_prediction: ResMut<TrimPrediction>,
+    hydrostatics: Res<Hydrostatics>,
+    ship_stats: Res<ShipStats>,
+    changed_parts: Query<(), Or<(Changed<BasePart>, Changed<AdjustableHull>, Changed<Turret>)>>,
+    mut removed_parts: RemovedComponents<BasePart>,
+    parts: Query<(&BasePart, Option<&AdjustableHull>)>,
+    part_registry: Res<PartRegistry>,
+){
+    if !changed_parts.is_empty() || removed_parts.read().count() > 0 {
+        trim_prediction.stale = true;
+    }
+    if !trim_prediction.requested { return; }
+    trim_prediction.requested = false;
+    trim_prediction.stale = false;
+
+    let Some(center_of_mass) = ship_stats.center_of_mass() else {
+        trim_prediction.result = Some(Err("the ship has no mass".to_owned()));
+        return;
+    };
+    let meshes = part_meshes(parts.iter(), &part_registry);
+    let volume = ship_stats.mass/hydrostatics.water_density;
+    let bevy_center_of_mass = unity_to_bevy_translation(&center_of_mass);
+    let equilibrium = match equilibrium(&meshes, volume, bevy_center_of_mass) {
+        Ok(equilibrium) => equilibrium,
+        Err(problem) => {
+            trim_prediction.result = Some(Err(problem));
+            return;
+        },
+    };
+
+    //the moments are taken about where the buoyancy is with the ship held level
+    let Some(level_waterline) = waterline_for_volume(&meshes, Vec3::Y, volume) else {
+        trim_prediction.result = Some(Err("the ship sinks when level".to_owned()));
+        return;
+    };
+    let level = submerged(&meshes, Vec3::Y*level_waterline, Vec3::Y);
+    let level_buoyancy = bevy_to_unity_translation(&level.centroid);
+    let imbalance = center_of_mass-level_buoyancy;
+
+    let distribution_range = ship_stats.parts().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), (_, stats)| {
+        return (min.min(stats.center.z), max.max(stats.center.z));
+    });
+    trim_prediction.result = Some(Ok(TrimResult {
+        equilibrium,
+        trim_drop: level.waterline_length*equilibrium.trim.to_radians().tan(),
+        center_of_mass,
+        level_buoyancy,
+        distribution: weight_distribution(ship_stats.parts().map(|(_, stats)| stats), distribution_range.0, distribution_range.1, DISTRIBUTION_BINS),
+        distribution_range,
+        heel_contributors: imbalance_contributors(ship_stats.parts(), level_buoyancy, 0, imbalance.x),
+        trim_contributors: imbalance_contributors(ship_stats.parts(), level_buoyancy, 2, imbalance.z),
+    }));
+}
+
+fn distribution_chart(ui: &mut egui::Ui, result: &TrimResult) {
+    let (response, painter) = ui.allocate_painter(egui::vec2(320.0, 120.0), egui::Sense::hover());
+    let rect = response.rect.shrink(4.0);
+    let text_color = ui.visuals().text_color();
+    let (min, max) = result.distribution_range;
+    let heaviest = result.distribution.iter().copied().fold(0.0, f32::max).max(f32::EPSILON);
+    let bar_width = rect.width()/(result.distribution.len() as f32);
+    let to_x = |z: f32| rect.left() + rect.width()*((z-min)/(max-min).max(f32::EPSILON)).clamp(0.0, 1.0);
+
+    for (bin, mass) in result.distribution.iter().enumerate() {
+        let left = rect.left()+bar_width*(bin as f32);
+        let bar = egui::Rect::from_min_max(
+            egui::pos2(left+1.0, rect.bottom()-rect.height()*mass/heaviest),
+            egui::pos2(left+bar_width-1.0, rect.bottom()),
+        );
+        painter.rect_filled(bar, 0.0, Color32::from_rgb(0, 150, 220));
+    }
+    //the ship sits level when these two line up
+    for (z, color) in [(result.center_of_mass.z, Color32::from_rgb(255, 80, 80)), (result.level_buoyancy.z, Color32::from_rgb(80, 220, 80))] {
+        painter.line_segment([egui::pos2(to_x(z), rect.top()), egui::pos2(to_x(z), rect.bottom())], Stroke::new(2.0, color));
+    }
+    painter.text(rect.left_bottom(), egui::Align2::LEFT_BOTTOM, format!("{:.1}",min), egui::FontId::monospace(10.0), text_color);
+    painter.text(rect.right_bottom(), egui::Align2::RIGHT_BOTTOM, format!("{:.1}",max), egui::FontId::monospace(10.0), text_color);
+
+    if let Some(hovered) = response.hover_pos() {
+        let bin = (((hovered.x-rect.left())/bar_width).floor().max(0.0) as usize).min(result.distribution.len()-1);
+        let bin_width = (max-min)/(result.distribution.len() as f32);
+        response.on_hover_text(format!(
+            "{:.1} to {:.1}: {:.1}",
+            min+bin_width*(bin as f32), min+bin_width*((bin+1) as f32), result.distribution[bin]
+        ));
+    }
+}
+
+pub fn trim_window(
+    mut contexts: EguiContexts,
+    mut trim_prediction: ResMut<TrimPrediction>,
+    editor_data: Res<EditorData>,
+    part_ids: Query<&BasePart>,
+    part_registry: Res<PartRegistry>,
+){
+    let trim_prediction = trim_prediction.as_mut();
+
+    egui::Window::new("Trim & Heel|纵倾与横倾")
+        .resizable(Vec2b::new(false,false))
+        .show(contexts.ctx_mut(), |ui| {
+            if ui.button("Work Out Trim & Heel|计算纵倾与横倾").clicked() {
+                trim_prediction.requested = true;
+            }
+            let Some(result) = &trim_prediction.result else {return;};
+            if trim_prediction.stale {
+                ui.label("Out of date, the ship changed|已过期，船只已修改");
+            }
+            let result = match result {
+                Ok(result) => result,
+                Err(problem) => {
+                    ui.label(problem);
+                    return;
+                },
+            };
+
+            egui::Grid::new("trim").num_columns(2).show(ui, |ui| {
+                ui.label("Heel (+x down)|横倾（+x向下）");
+                ui.label(format!("{:.2}°",result.equilibrium.heel));
+                ui.end_row();
+                ui.label("Trim (+z down)|纵倾（+z向下）");
+                ui.label(format!("{:.2}° ({:.2})",result.equilibrium.trim,result.trim_drop));
+                ui.end_row();
+            });
+            if !result.equilibrium.stable {
+                ui.colored_label(Color32::from_rgb(255, 80, 80), "Unstable, it won't stay there|不稳定，无法保持");
+            }
+
+            ui.separator();
+            ui.label("Weight Along the Length|沿船长的重量分布");
+            distribution_chart(ui, result);
+
+            ui.checkbox(&mut trim_prediction.highlight, "Highlight|高亮");
+            for (title, contributors) in [("Heeling It Most|横倾主因", &result.heel_contributors), ("Trimming It Most|纵倾主因", &result.trim_contributors)] {
+                ui.collapsing(title, |ui| {
+                    egui::Grid::new(title).num_columns(3).show(ui, |ui| {
+                        for contributor in contributors {
+                            let name = part_ids.get(contributor.entity).ok()
+                                .and_then(|base_part| part_registry.parts.get(&base_part.id))
+                                .map_or("?", |part_data| part_data.part_name.get(editor_data.language));
+                            ui.label(name);
+                            ui.label(format!("{:.1}",contributor.mass));
+                            ui.label(format!("{:.1}",contributor.moment));
+                            ui.end_row();
+                        }
+                    });
+                });
+            }
+        });
+}
+
+pub fn highlight_contributors(
+    trim_prediction: Res<TrimPrediction>,
+    parts: Query<(&BasePart, Option<&AdjustableHull>)>,
+    part_registry: Res<PartRegistry>,
+    mut gizmo: Gizmos,
+){
+    if !trim_prediction.highlight { return; }
+    let Some(Ok(result)) = &trim_prediction.result else {return;};
+    for contributor in result.heel_contributors.iter().chain(&result.trim_contributors) {
+        let Ok((base_part, adjustable_hull)) = parts.get(contributor.entity) else {continue;};
+        let Some(part_data) = part_registry.parts.get(&base_part.id) else {continue;};
+        gizmo.cuboid(get_collider(base_part, adjustable_hull, part_data), Color::srgb_u8(255, 80, 80));
+    }
+}